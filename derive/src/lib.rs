@@ -0,0 +1,150 @@
+//! `#[derive(FromJsValue, IntoJsValue)]` for structs with named fields. Each
+//! field maps to the property of the same name, or the one given with
+//! `#[js(rename = "name")]`.
+//!
+//! ```ignore
+//! #[derive(FromJsValue, IntoJsValue)]
+//! struct Stat {
+//!     size: u64,
+//!     #[js(rename = "isFile")]
+//!     is_file: bool,
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(FromJsValue, attributes(js))]
+pub fn derive_from_js_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_js_value(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(IntoJsValue, attributes(js))]
+pub fn derive_into_js_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_into_js_value(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    ident: syn::Ident,
+    key: String,
+}
+
+fn named_fields(input: &DeriveInput) -> syn::Result<Vec<Field>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs are supported",
+            ))
+        }
+    };
+
+    let mut result = vec![];
+    for field in fields {
+        let ident = field.ident.clone().unwrap();
+        let mut key = ident.to_string();
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("js")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(syn::Error::new_spanned(meta, "expected #[js(...)]")),
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                        match nv.lit {
+                            Lit::Str(s) => key = s.value(),
+                            lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                        }
+                    }
+                    nested => {
+                        return Err(syn::Error::new_spanned(
+                            nested,
+                            "unknown js attribute, expected `rename = \"...\"`",
+                        ))
+                    }
+                }
+            }
+        }
+        result.push(Field { ident, key });
+    }
+    Ok(result)
+}
+
+fn add_bounds(input: &mut DeriveInput, bound: syn::Path) {
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+}
+
+fn expand_from_js_value(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(&input)?;
+    add_bounds(&mut input, parse_quote!(::drop::FromJsValue));
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let reads = fields.iter().map(|Field { ident, key }| {
+        quote! {
+            #ident: {
+                let value = ::drop::AsObject::get(obj, #key);
+                ::drop::FromJsValue::from_js_value(ctx, &value).map_err(|e| e.within(#key))?
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::drop::FromJsValue for #name #ty_generics #where_clause {
+            fn from_js_value(
+                ctx: &mut ::drop::Context,
+                value: &::drop::JsValue,
+            ) -> ::std::result::Result<Self, ::drop::ConvertError> {
+                match value {
+                    ::drop::JsValue::Object(obj) => ::std::result::Result::Ok(#name {
+                        #(#reads,)*
+                    }),
+                    _ => ::std::result::Result::Err(::drop::ConvertError::new("object", value)),
+                }
+            }
+        }
+    })
+}
+
+fn expand_into_js_value(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(&input)?;
+    add_bounds(&mut input, parse_quote!(::drop::IntoJsValue));
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let writes = fields.iter().map(|Field { ident, key }| {
+        quote! {
+            let value = ::drop::IntoJsValue::into_js_value(self.#ident, ctx);
+            ::drop::AsObject::set(&mut obj, #key, value);
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::drop::IntoJsValue for #name #ty_generics #where_clause {
+            fn into_js_value(self, ctx: &mut ::drop::Context) -> ::drop::JsValue {
+                let mut obj = ctx.new_object();
+                #(#writes)*
+                ::drop::JsValue::Object(obj)
+            }
+        }
+    })
+}
@@ -0,0 +1,182 @@
+use crate::js_bytecode::is_bytecode_file;
+use crate::resolver;
+use crate::transpiler::tsx_to_bundle_module;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+pub enum BundleFormat {
+    /// An ES module that imports the built-ins it needs and default exports
+    /// the `exports` of the entry module.
+    ESModule,
+    /// A plain script that loads the built-ins with `import()` and runs the
+    /// entry module.
+    Iife,
+}
+
+// Every bundled module becomes a factory in `__drop_modules`, keyed by its
+// resolved path, next to a map from the specifiers it requires to their keys.
+const PRELUDE: &str = r#"const __drop_cache = {};
+function __drop_load(id) {
+	let module = __drop_cache[id];
+	if (!module) {
+		const [factory, deps] = __drop_modules[id];
+		module = __drop_cache[id] = { exports: {} };
+		const require = (name) => {
+			if (name in __drop_externals) return __drop_externals[name];
+			if (name in deps) return __drop_load(deps[name]);
+			throw new Error(`Cannot find module '${name}' in bundle`);
+		};
+		const dirname = id.includes("/") ? id.slice(0, id.lastIndexOf("/")) : ".";
+		factory.call(module.exports, module.exports, require, module, id, dirname);
+	}
+	return module.exports;
+}
+"#;
+
+struct BundledModule {
+    code: String,
+    deps: BTreeMap<String, String>,
+}
+
+/// Built-ins shipped with drop (`fs`, `path`, ...) and native `_name` modules
+/// are left to the runtime instead of being copied into the bundle.
+fn is_external(specifier: &str) -> bool {
+    specifier.starts_with('_') || resolver::is_embedded_module(specifier)
+}
+
+/// Joins a relative `specifier` onto the directory of `base` the same way
+/// QuickJS' default module name normalizer does, so that keys in the bundle
+/// match the names modules get when run unbundled.
+fn normalize(base: &str, specifier: &str) -> String {
+    if !specifier.starts_with('.') {
+        return specifier.to_string();
+    }
+    let mut parts: Vec<&str> = base.split('/').collect();
+    parts.pop();
+    for part in specifier.split('/') {
+        match part {
+            "." => {}
+            ".." if !matches!(parts.last(), None | Some(&".") | Some(&"..")) => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Walks the import graph of `entry` through `resolver` and returns a single
+/// script containing every module it reaches.
+pub fn bundle(entry: &str, format: &BundleFormat) -> Result<String, Error> {
+    let entry = resolver::resolve(entry)?;
+    let mut modules: BTreeMap<String, BundledModule> = BTreeMap::new();
+    let mut externals: Vec<String> = vec![];
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert(entry.clone());
+    queue.push_back(entry.clone());
+
+    while let Some(key) = queue.pop_front() {
+        if is_bytecode_file(&key) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot bundle bytecode file: {}", key),
+            ));
+        }
+        let source = fs::read_to_string(&key)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", key, e)))?;
+        let (code, specifiers) = tsx_to_bundle_module(Some(&key), &source)
+            .map_err(|e| Error::new(ErrorKind::Other, format!("{}: {}", key, e)))?;
+
+        let mut deps = BTreeMap::new();
+        for specifier in specifiers {
+            if is_external(&specifier) {
+                if !externals.contains(&specifier) {
+                    externals.push(specifier);
+                }
+                continue;
+            }
+            let dep = resolver::resolve(&normalize(&key, &specifier))?;
+            if seen.insert(dep.clone()) {
+                queue.push_back(dep.clone());
+            }
+            deps.insert(specifier, dep);
+        }
+        modules.insert(key, BundledModule { code, deps });
+    }
+
+    let mut out = String::new();
+    let external_value = |i: usize| match format {
+        BundleFormat::ESModule => format!("__drop_external_{}", i),
+        BundleFormat::Iife => format!("await import({})", quote(&externals[i])),
+    };
+    if let BundleFormat::ESModule = format {
+        for (i, name) in externals.iter().enumerate() {
+            out.push_str(&format!(
+                "import * as __drop_external_{} from {};\n",
+                i,
+                quote(name)
+            ));
+        }
+    } else {
+        out.push_str("(async () => {\n");
+    }
+
+    // module namespaces lack `__esModule`, which the CommonJS interop checks
+    out.push_str("const __drop_externals = {\n");
+    for (i, name) in externals.iter().enumerate() {
+        out.push_str(&format!(
+            "\t{}: Object.assign({{ __esModule: true }}, {}),\n",
+            quote(name),
+            external_value(i)
+        ));
+    }
+    out.push_str("};\n");
+
+    out.push_str("const __drop_modules = {\n");
+    for (key, module) in &modules {
+        let deps: Vec<String> = module
+            .deps
+            .iter()
+            .map(|(specifier, dep)| format!("{}: {}", quote(specifier), quote(dep)))
+            .collect();
+        out.push_str(&format!(
+            "\t{}: [function (exports, require, module, __filename, __dirname) {{\n{}\n}}, {{ {} }}],\n",
+            quote(key),
+            module.code,
+            deps.join(", ")
+        ));
+    }
+    out.push_str("};\n");
+    out.push_str(PRELUDE);
+
+    match format {
+        BundleFormat::ESModule => {
+            out.push_str(&format!("export default __drop_load({});\n", quote(&entry)));
+        }
+        BundleFormat::Iife => {
+            out.push_str(&format!("__drop_load({});\n", quote(&entry)));
+            out.push_str(
+                "})().catch((e) => {\n\tconsole.error(e);\n\tprocess.exitCode = 1;\n});\n",
+            );
+        }
+    }
+    Ok(out)
+}
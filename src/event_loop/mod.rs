@@ -1,17 +1,25 @@
 use wasi::Subscription;
 
-use crate::{quickjs_sys as qjs, Context, JsValue};
+use crate::modules_rs::fs::open_parent;
+use crate::{quickjs_sys as qjs, resolver, Context, JsValue};
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
 use std::collections::{HashMap, LinkedList};
+use std::future::Future;
 use std::io;
 use std::mem::{zeroed, ManuallyDrop};
 use std::ops::{Add, Div};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Wake, Waker};
+use std::time::Instant;
 
 pub enum PollResult {
     Timeout,
     Read(Vec<u8>),
-    Error(io::Error),
+    Error(wasi::Errno),
     Write(usize),
 }
 
@@ -114,7 +122,7 @@ impl IoSelector {
         self.tasks.get_mut(id)?.take()
     }
 
-    pub fn poll(&mut self, ctx: &mut qjs::Context) -> io::Result<usize> {
+    pub fn poll(&mut self, ctx: &mut qjs::Context, deadline: Option<Instant>) -> io::Result<usize> {
         let mut subscription_vec = Vec::with_capacity(self.tasks.len());
         for (i, timeout) in self.tasks.iter().enumerate() {
             if let Some(task) = timeout {
@@ -136,6 +144,18 @@ impl IoSelector {
             return Ok(0);
         }
 
+        // wake up at the deadline even if every task waits longer, its event
+        // has no task and is skipped below
+        if let Some(deadline) = deadline {
+            let timeout = TimeoutTask {
+                timeout: deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_nanos(),
+                callback: Box::new(|_, _| {}),
+            };
+            subscription_vec.push(timeout.as_subscription(usize::MAX));
+        }
+
         let mut revent: [wasi::Event; 1] = unsafe { zeroed() };
 
         let n = unsafe {
@@ -167,8 +187,7 @@ impl IoSelector {
                         wasi::EVENTTYPE_FD_READ,
                     ) => {
                         if event.error.raw() > 0 {
-                            let e = io::Error::from_raw_os_error(event.error.raw().into());
-                            callback(ctx, PollResult::Error(e));
+                            callback(ctx, PollResult::Error(event.error));
                             continue;
                         }
                         let len = len as usize; // len.min(event.fd_readwrite.nbytes) as usize;
@@ -202,9 +221,7 @@ impl IoSelector {
                                     buf.resize(rlen, 0);
                                     PollResult::Read(buf)
                                 }
-                                Err(e) => {
-                                    PollResult::Error(io::Error::from_raw_os_error(e.raw() as i32))
-                                }
+                                Err(e) => PollResult::Error(e),
                             },
                         );
                     }
@@ -218,17 +235,13 @@ impl IoSelector {
                         wasi::EVENTTYPE_FD_WRITE,
                     ) => {
                         if event.error.raw() > 0 {
-                            let e = io::Error::from_raw_os_error(event.error.raw().into());
-                            callback(ctx, PollResult::Error(e));
+                            callback(ctx, PollResult::Error(event.error));
                             continue;
                         }
                         if pos != -1 {
                             let res = unsafe { wasi::fd_seek(fd as u32, pos, wasi::WHENCE_SET) };
                             if let Err(e) = res {
-                                callback(
-                                    ctx,
-                                    PollResult::Error(io::Error::from_raw_os_error(e.raw() as i32)),
-                                );
+                                callback(ctx, PollResult::Error(e));
                                 continue;
                             }
                         }
@@ -245,9 +258,7 @@ impl IoSelector {
                             ctx,
                             match res {
                                 Ok(len) => PollResult::Write(len),
-                                Err(e) => {
-                                    PollResult::Error(io::Error::from_raw_os_error(e.raw() as i32))
-                                }
+                                Err(e) => PollResult::Error(e),
                             },
                         );
                     }
@@ -259,20 +270,468 @@ impl IoSelector {
     }
 }
 
+/// Callbacks run by the event loop have no JS caller to throw into, so an
+/// exception escaping one is kept here for `js_loop` to return. Only the
+/// first one is kept.
+pub(crate) fn check_callback_result(ctx: &mut qjs::Context, res: JsValue) {
+    if let JsValue::Exception(_) = res {
+        let e = ctx.take_exception();
+        if let Some(event_loop) = ctx.event_loop() {
+            event_loop.uncaught_exception.get_or_insert(e);
+        }
+    }
+}
+
+/// Modification time of `path`, or `None` if it can't be stat'ed (e.g. it was
+/// deleted, which also counts as a change).
+fn file_mtime(path: &str) -> Option<u64> {
+    let (dir, file) = open_parent(path).ok()?;
+    let stat =
+        unsafe { wasi::path_filestat_get(dir, wasi::LOOKUPFLAGS_SYMLINK_FOLLOW, file.as_str()) };
+    stat.ok().map(|stat| stat.mtim)
+}
+
+/// WASI has no file change notifications, so `--watch` remembers the mtime of
+/// every file the module loaders read and compares them on a timer.
+/// The files recorded by `EventLoop::watch_loaded_files` and their mtimes.
+pub struct FileWatcher {
+    interval: std::time::Duration,
+    files: Vec<(String, Option<u64>)>,
+    changed: bool,
+}
+
+impl FileWatcher {
+    fn is_changed(&self) -> bool {
+        self.files
+            .iter()
+            .any(|(path, mtime)| file_mtime(path) != *mtime)
+    }
+
+    /// Blocks, checking the files every interval, until one of them changed.
+    /// Used once the runtime that loaded them is gone.
+    pub fn wait_for_change(&self) {
+        while !self.changed && !self.is_changed() {
+            std::thread::sleep(self.interval);
+        }
+    }
+}
+
+/// Stops the scripts of a runtime from another part of the embedder, see
+/// `Runtime::interrupt_handle`.
+#[derive(Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Makes running JS throw an uncatchable `InternalError: interrupted` and
+    /// `js_loop` return an error of kind `Interrupted`.
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// What `js_loop` does with a promise that was rejected and still has no
+/// handler once the job queue is drained, like Node's
+/// `--unhandled-rejections`. `rejectionHandled` is emitted on `process` in
+/// every mode when such a promise gets a handler later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnhandledRejections {
+    /// Emits `unhandledRejection` on `process`, and without a listener raises
+    /// the reason as an uncaught exception.
+    Throw,
+    /// Raises the reason as an uncaught exception.
+    Strict,
+    /// Emits `unhandledRejection` and always prints a warning.
+    Warn,
+    /// Only emits `unhandledRejection`.
+    None,
+}
+
+impl Default for UnhandledRejections {
+    fn default() -> Self {
+        UnhandledRejections::Throw
+    }
+}
+
+impl std::str::FromStr for UnhandledRejections {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "throw" => Ok(UnhandledRejections::Throw),
+            "strict" => Ok(UnhandledRejections::Strict),
+            "warn" => Ok(UnhandledRejections::Warn),
+            "none" => Ok(UnhandledRejections::None),
+            _ => Err(format!("invalid unhandled rejections mode: {}", s)),
+        }
+    }
+}
+
+/// Promises the host rejection tracker saw, kept until `js_loop` reports
+/// them.
+#[derive(Default)]
+pub(crate) struct Rejections {
+    pub mode: UnhandledRejections,
+    /// (promise, reason) rejected without a handler since the last report
+    pub pending: Vec<(JsValue, JsValue)>,
+    /// promises `unhandledRejection` was emitted for that could still get a
+    /// handler, see `forget_unreachable`
+    pub reported: Vec<JsValue>,
+    /// reported promises that got a handler since the last report
+    pub handled: Vec<JsValue>,
+}
+
+impl Rejections {
+    pub fn track(&mut self, promise: JsValue, reason: JsValue, is_handled: bool) {
+        if !is_handled {
+            self.pending.push((promise, reason));
+        } else if let Some(i) = self.pending.iter().position(|(p, _)| *p == promise) {
+            // handled within the same turn, nothing to report
+            self.pending.remove(i);
+        } else if let Some(i) = self.reported.iter().position(|p| *p == promise) {
+            self.handled.push(self.reported.remove(i));
+        }
+    }
+}
+
+struct PendingState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// A future that resolves once the matching `Completer` is called, usually
+/// from an event loop callback. Made with `pending`.
+pub struct Pending<T>(Rc<RefCell<PendingState<T>>>);
+
+/// Resolves the `Pending` it was made with.
+pub struct Completer<T>(Rc<RefCell<PendingState<T>>>);
+
+pub fn pending<T>() -> (Completer<T>, Pending<T>) {
+    let state = Rc::new(RefCell::new(PendingState {
+        value: None,
+        waker: None,
+    }));
+    (Completer(state.clone()), Pending(state))
+}
+
+impl<T> Completer<T> {
+    pub fn complete(self, value: T) {
+        let mut state = self.0.as_ref().borrow_mut();
+        state.value = Some(value);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for Pending<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<T> {
+        let mut state = self.0.as_ref().borrow_mut();
+        match state.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// What a spawned future finishes with: the part of its work that needs the
+/// context, such as settling a promise.
+pub type Completion = Box<dyn FnOnce(&mut qjs::Context)>;
+
+type LocalTask = Pin<Box<dyn Future<Output = Completion>>>;
+
+// Wakes a task by queueing its index for the next `run_once`. Everything
+// runs on one thread, the `Mutex` only satisfies `Waker`'s bounds.
+struct TaskWaker {
+    id: usize,
+    woken: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if let Ok(mut woken) = self.woken.lock() {
+            woken.push(self.id);
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct EventLoop {
     next_tick_queue: LinkedList<Box<dyn FnOnce(&mut qjs::Context)>>,
     io_selector: IoSelector,
+    tasks: Vec<Option<LocalTask>>,
+    running_task: Option<usize>,
+    woken_tasks: Arc<Mutex<Vec<usize>>>,
+    uncaught_exception: Option<qjs::JsError>,
+    pub(crate) rejections: Rejections,
+    watcher: Option<FileWatcher>,
+    interrupt: InterruptHandle,
+    deadline: Option<Instant>,
+    exit_code: Option<i32>,
 }
 
 impl EventLoop {
     pub fn run_once(&mut self, ctx: &mut qjs::Context) -> io::Result<usize> {
         let n = self.run_tick_task(ctx);
         if n > 0 {
-            Ok(n)
-        } else {
-            self.io_selector.poll(ctx)
+            return Ok(n);
+        }
+        let n = self.run_woken_tasks(ctx);
+        if n > 0 {
+            return Ok(n);
+        }
+        self.io_selector.poll(ctx, self.deadline)
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Whether the runtime was interrupted or ran past its deadline. Checked
+    /// by the QuickJS interrupt handler and by `js_loop`.
+    pub fn is_interrupted(&self) -> bool {
+        if self.interrupt.0.load(Ordering::SeqCst) {
+            return true;
         }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.interrupt.interrupt();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether anything is left to run. A spawned future that is parked
+    /// doesn't count, as only the timers, I/O and jobs counted here can wake
+    /// it, and one that never settles must not keep the process alive.
+    pub fn has_pending_tasks(&self) -> bool {
+        let has_woken = self
+            .woken_tasks
+            .lock()
+            .map_or(false, |woken| !woken.is_empty());
+        !self.next_tick_queue.is_empty()
+            || self.io_selector.tasks.iter().any(Option::is_some)
+            || has_woken
+    }
+
+    pub fn take_uncaught_exception(&mut self) -> Option<qjs::JsError> {
+        self.uncaught_exception.take()
+    }
+
+    pub fn set_unhandled_rejections(&mut self, mode: UnhandledRejections) {
+        self.rejections.mode = mode;
+    }
+
+    /// Starts recording the files passed to `record_loaded_file` and checking
+    /// them for changes every `interval`. The check is a timer task, so the
+    /// loop keeps running until `take_watched_file_change` reports a change.
+    pub fn watch_loaded_files(&mut self, interval: std::time::Duration) {
+        self.watcher = Some(FileWatcher {
+            interval,
+            files: vec![],
+            changed: false,
+        });
+        self.schedule_watch();
+    }
+
+    /// Called by the module loaders for every module read from disk. Does
+    /// nothing unless `watch_loaded_files` was called.
+    pub fn record_loaded_file(&mut self, module_name: &str) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            if module_name.starts_with('_') || resolver::is_embedded_module(module_name) {
+                return;
+            }
+            if let Ok(path) = resolver::resolve(module_name) {
+                if !watcher.files.iter().any(|(p, _)| *p == path) {
+                    let mtime = file_mtime(&path);
+                    watcher.files.push((path, mtime));
+                }
+            }
+        }
+    }
+
+    /// Whether `watch_loaded_files` was called.
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// The recorded files, for `FileWatcher::wait_for_change` after this
+    /// runtime is torn down. Stops watching.
+    pub fn take_watched_files(&mut self) -> Option<FileWatcher> {
+        self.watcher.take()
+    }
+
+    /// Ends the current run of a watched script, which is what `exit` does
+    /// in watch mode instead of ending the process. The runtime is
+    /// interrupted, and `take_exit_code` tells that apart from a timeout.
+    pub fn exit_run(&mut self, code: i32) {
+        self.exit_code = Some(code);
+        self.interrupt.interrupt();
+    }
+
+    pub fn take_exit_code(&mut self) -> Option<i32> {
+        self.exit_code.take()
+    }
+
+    pub fn take_watched_file_change(&mut self) -> bool {
+        match self.watcher.as_mut() {
+            Some(watcher) => std::mem::take(&mut watcher.changed),
+            None => false,
+        }
+    }
+
+    fn schedule_watch(&mut self) {
+        if let Some(watcher) = &self.watcher {
+            self.io_selector.add_task(PollTask::Timeout(TimeoutTask {
+                timeout: watcher.interval.as_nanos(),
+                callback: Box::new(|ctx, _res| {
+                    if let Some(event_loop) = ctx.event_loop() {
+                        event_loop.check_watched_files();
+                    }
+                }),
+            }));
+        }
+    }
+
+    fn check_watched_files(&mut self) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            if watcher.is_changed() {
+                watcher.changed = true;
+            } else {
+                self.schedule_watch();
+            }
+        }
+    }
+
+    /// Runs `future` on the event loop. It is polled on the next `run_once`
+    /// and again whenever its waker is woken, and the `Completion` it finishes
+    /// with is called with the context.
+    ///
+    /// Futures are only woken by what the event loop drives, e.g. `sleep`,
+    /// `read`, `write` or a `Completer` called from a callback. `js_loop`
+    /// returns once nothing else is left, even if a future is still pending.
+    pub fn spawn_local<F: Future<Output = Completion> + 'static>(&mut self, future: F) -> usize {
+        let task: LocalTask = Box::pin(future);
+        let running = self.running_task;
+        let free = self
+            .tasks
+            .iter()
+            .enumerate()
+            .position(|(id, task)| task.is_none() && Some(id) != running);
+        let id = match free {
+            Some(id) => {
+                self.tasks[id] = Some(task);
+                id
+            }
+            None => {
+                self.tasks.push(Some(task));
+                self.tasks.len() - 1
+            }
+        };
+        if let Ok(mut woken) = self.woken_tasks.lock() {
+            woken.push(id);
+        }
+        id
+    }
+
+    fn run_woken_tasks(&mut self, ctx: &mut qjs::Context) -> usize {
+        let woken = match self.woken_tasks.lock() {
+            Ok(mut woken) => std::mem::take(&mut *woken),
+            Err(_) => return 0,
+        };
+        let mut n = 0;
+        for id in woken {
+            // taken out while polling, the future may reach this event loop;
+            // `running_task` keeps `spawn_local` off the empty slot
+            let mut task = match self.tasks.get_mut(id).and_then(Option::take) {
+                Some(task) => task,
+                None => continue,
+            };
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                woken: self.woken_tasks.clone(),
+            }));
+            n += 1;
+            self.running_task = Some(id);
+            let poll = task
+                .as_mut()
+                .poll(&mut std::task::Context::from_waker(&waker));
+            self.running_task = None;
+            match poll {
+                Poll::Ready(completion) => completion(ctx),
+                Poll::Pending => self.tasks[id] = Some(task),
+            }
+        }
+        n
+    }
+
+    /// A future that resolves after `timeout`.
+    pub fn sleep(&mut self, timeout: std::time::Duration) -> Pending<()> {
+        let (completer, pending) = pending();
+        self.io_selector.add_task(PollTask::Timeout(TimeoutTask {
+            timeout: timeout.as_nanos(),
+            callback: Box::new(move |_ctx, _res| completer.complete(())),
+        }));
+        pending
+    }
+
+    /// `fd_read` as a future.
+    pub fn read(
+        &mut self,
+        fd: std::os::wasi::io::RawFd,
+        pos: i64,
+        len: u64,
+    ) -> Pending<Result<Vec<u8>, wasi::Errno>> {
+        let (completer, pending) = pending();
+        self.fd_read(
+            fd,
+            pos,
+            len,
+            Box::new(move |_ctx, res| {
+                completer.complete(match res {
+                    PollResult::Read(data) => Ok(data),
+                    PollResult::Error(e) => Err(e),
+                    _ => Err(wasi::ERRNO_IO),
+                })
+            }),
+        );
+        pending
+    }
+
+    /// `fd_write` as a future.
+    pub fn write(
+        &mut self,
+        fd: std::os::wasi::io::RawFd,
+        pos: i64,
+        buf: Vec<u8>,
+    ) -> Pending<Result<usize, wasi::Errno>> {
+        let (completer, pending) = pending();
+        self.fd_write(
+            fd,
+            pos,
+            buf,
+            Box::new(move |_ctx, res| {
+                completer.complete(match res {
+                    PollResult::Write(len) => Ok(len),
+                    PollResult::Error(e) => Err(e),
+                    _ => Err(wasi::ERRNO_IO),
+                })
+            }),
+        );
+        pending
     }
 
     fn run_tick_task(&mut self, ctx: &mut qjs::Context) -> usize {
@@ -292,11 +751,12 @@ impl EventLoop {
     ) -> usize {
         let timeout_task = PollTask::Timeout(TimeoutTask {
             timeout: timeout.as_nanos().into(),
-            callback: Box::new(move |_ctx, _res| {
-                match args {
+            callback: Box::new(move |ctx, _res| {
+                let res = match args {
                     Some(argv) => callback.call(&argv),
                     None => callback.call(&[]),
                 };
+                check_callback_result(ctx, res);
             }),
         });
         self.io_selector.add_task(timeout_task)
@@ -1,11 +1,18 @@
 #![allow(dead_code, unused_imports, unused_must_use)]
 #[macro_use]
 extern crate lazy_static;
+// lets `drop_derive` expansions name `::drop::` inside this crate too
+extern crate self as drop;
 
+pub mod bundler;
 mod event_loop;
 mod modules_rs;
 pub mod quickjs_sys;
+pub mod repl;
 
-pub use event_loop::EventLoop;
+pub use event_loop::{
+    pending, Completer, Completion, EventLoop, FileWatcher, InterruptHandle, Pending,
+    UnhandledRejections,
+};
 
 pub use quickjs_sys::*;
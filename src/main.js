@@ -4,7 +4,7 @@
 })();
 (async () => {
 	const _process = await import("process");
-	globalThis.process = _process;
+	globalThis.process = _process.default;
 })();
 (async () => {
 	const _console = await import("console");
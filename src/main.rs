@@ -2,70 +2,502 @@
 
 extern crate libc;
 
-use drop::{quickjs_sys::resolver, quickjs_sys::transpiler, Context, Runtime, *};
-use once_cell::sync::Lazy;
-use std::{ffi::CString, sync::Mutex};
+use drop::{
+    bundler::{bundle, BundleFormat},
+    quickjs_sys::js_bytecode,
+    quickjs_sys::resolver,
+    quickjs_sys::transpiler,
+    repl::Repl,
+    Context, Runtime, *,
+};
+use std::ffi::CString;
+use std::io::Read;
+use std::time::{Duration, Instant};
+use transpiler::{tsx_to_js_str, OutputType};
 
-fn args_parse() -> (String, Vec<String>) {
+/// Resource limits and settings applied to every runtime the CLI creates.
+#[derive(Default, Clone, Copy)]
+struct Limits {
+    /// MiB
+    max_memory: Option<usize>,
+    /// KiB
+    stack_size: Option<usize>,
+    /// KiB
+    gc_threshold: Option<usize>,
+    /// milliseconds
+    timeout: Option<u64>,
+    unhandled_rejections: UnhandledRejections,
+}
+
+impl Limits {
+    fn new_runtime(&self) -> Runtime {
+        let mut rt = Runtime::new();
+        if let Some(mb) = self.max_memory {
+            rt.set_memory_limit(mb * 1024 * 1024);
+        }
+        if let Some(kb) = self.stack_size {
+            rt.set_max_stack_size(kb * 1024);
+        }
+        if let Some(kb) = self.gc_threshold {
+            rt.set_gc_threshold(kb * 1024);
+        }
+        if let Some(ms) = self.timeout {
+            rt.set_deadline(Instant::now() + Duration::from_millis(ms));
+        }
+        rt.set_unhandled_rejections(self.unhandled_rejections);
+        rt
+    }
+}
+
+struct Args {
+    limits: Limits,
+    check: bool,
+    watch: bool,
+    file: String,
+    eval: Option<String>,
+    print: Option<String>,
+    rest: Vec<String>,
+}
+
+fn args_parse() -> Args {
     use argparse::ArgumentParser;
-    let mut file_path = String::new();
-    let mut rest_args: Vec<String> = vec![];
+    let mut args = Args {
+        limits: Limits::default(),
+        check: false,
+        watch: false,
+        file: String::new(),
+        eval: None,
+        print: None,
+        rest: vec![],
+    };
     {
         let mut arg_parser = ArgumentParser::new();
+        arg_parser.refer(&mut args.eval).add_option(
+            &["-e", "--eval"],
+            argparse::StoreOption,
+            "evaluate script",
+        );
+        arg_parser.refer(&mut args.print).add_option(
+            &["-p", "--print"],
+            argparse::StoreOption,
+            "evaluate script and print result",
+        );
+        arg_parser.refer(&mut args.check).add_option(
+            &["-c", "--check"],
+            argparse::StoreTrue,
+            "syntax check the given scripts without executing",
+        );
+        arg_parser.refer(&mut args.watch).add_option(
+            &["--watch"],
+            argparse::StoreTrue,
+            "restart the script whenever a module it loaded changes",
+        );
+        arg_parser.refer(&mut args.limits.max_memory).add_option(
+            &["--max-memory"],
+            argparse::StoreOption,
+            "limit the JS heap to this many MiB",
+        );
+        arg_parser.refer(&mut args.limits.stack_size).add_option(
+            &["--stack-size"],
+            argparse::StoreOption,
+            "limit the native stack used by JS to this many KiB (below 8192)",
+        );
+        arg_parser.refer(&mut args.limits.gc_threshold).add_option(
+            &["--gc-threshold"],
+            argparse::StoreOption,
+            "run the garbage collector after this many KiB were allocated",
+        );
+        arg_parser.refer(&mut args.limits.timeout).add_option(
+            &["--timeout"],
+            argparse::StoreOption,
+            "interrupt the script after this many milliseconds",
+        );
         arg_parser
-            .refer(&mut file_path)
-            .add_argument(
-                "file",
+            .refer(&mut args.limits.unhandled_rejections)
+            .add_option(
+                &["--unhandled-rejections"],
                 argparse::Store,
-                "input script (*.[cm][ts|js][x] or *.zrc)",
-            )
-            .required();
-        arg_parser.refer(&mut rest_args).add_argument(
+                "throw (default), strict, warn or none",
+            );
+        arg_parser.refer(&mut args.file).add_argument(
+            "file",
+            argparse::Store,
+            "input script (*.[cm][ts|js][x] or *.zrc), - to read from stdin, starts a REPL if omitted; \
+             write ./compile or ./bundle for a script named like a subcommand",
+        );
+        arg_parser.refer(&mut args.rest).add_argument(
             "args",
             argparse::List,
             "additional arguments for runtime",
         );
+        arg_parser.stop_on_first_argument(true);
         arg_parser.parse_args_or_exit();
     }
-    (file_path, rest_args)
+    args
+}
+
+fn init_globals(ctx: &mut Context, args: Vec<String>) {
+    ctx.put_args(args);
+    if let Err(e) = ctx.eval_global_str(include_str!("./main.js").into()) {
+        eprintln!("{:#}", e);
+    }
+    ctx.promise_loop_poll();
+}
+
+/// Exit code of a script stopped by `--timeout`, same as timeout(1).
+const INTERRUPTED_EXIT_CODE: i32 = 124;
+
+/// Reports `e` and returns the exit code for it. An interrupted runtime can't
+/// run the `exit` listeners anymore, so it exits right away with its own code.
+fn exit_on_error(ctx: &mut Context, e: JsError) -> i32 {
+    if e.kind == JsErrorKind::Interrupted {
+        eprintln!("script execution was interrupted");
+        INTERRUPTED_EXIT_CODE
+    } else {
+        eprintln!("{:#}", e);
+        ctx.emit_exit(Some(1))
+    }
+}
+
+/// Drives the event loop until the script is done and returns the code the
+/// process should exit with, following Node: `beforeExit` is emitted each time
+/// the loop drains (and may schedule more work), then `exit` is emitted once.
+/// An uncaught error skips `beforeExit` and exits with 1.
+/// An interrupted script exits with `INTERRUPTED_EXIT_CODE`.
+fn run_to_exit(ctx: &mut Context) -> i32 {
+    loop {
+        if let Err(e) = ctx.js_loop() {
+            return exit_on_error(ctx, e);
+        }
+        if let JsValue::Exception(_) = ctx.emit_before_exit() {
+            let e = ctx.take_exception();
+            return exit_on_error(ctx, e);
+        }
+        if ctx.is_idle() {
+            return ctx.emit_exit(None);
+        }
+    }
+}
+
+fn set_filename(ctx: &mut Context, filename: &str) {
+    let filename_val = ctx.new_string(filename);
+    ctx.get_global().set("__filename", filename_val.into());
+}
+
+fn run_module(ctx: &mut Context, filename: &str, code: String) -> i32 {
+    set_filename(ctx, filename);
+    match ctx.eval_module_str(code, filename) {
+        Ok(_) => run_to_exit(ctx),
+        Err(e) => exit_on_error(ctx, e),
+    }
+}
+
+fn run_bytecode(ctx: &mut Context, filename: &str, buf: &[u8]) -> i32 {
+    set_filename(ctx, filename);
+    match ctx.eval_bytecode(buf) {
+        Ok(_) => run_to_exit(ctx),
+        Err(e) => exit_on_error(ctx, e),
+    }
+}
+
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Runs `file` in a fresh runtime until one of the files it loaded changes,
+/// then starts over. Failures and `process.exit()` are reported and end the
+/// run, but keep the watch going.
+fn run_watch(file: &str, rest: Vec<String>, limits: Limits) -> ! {
+    loop {
+        if let Some(watcher) = run_watched(file, rest.clone(), limits) {
+            eprintln!("Waiting for file changes before restarting '{}'", file);
+            watcher.wait_for_change();
+        }
+        eprintln!("Restarting '{}'", file);
+    }
+}
+
+/// One run of `run_watch`. Returns the files to wait on when the script
+/// failed or exited, after its runtime was torn down so that none of its
+/// timers or I/O keep going, and `None` when a file changed while it ran.
+fn run_watched(file: &str, rest: Vec<String>, limits: Limits) -> Option<FileWatcher> {
+    let mut rt = limits.new_runtime();
+    let mut ctx = rt.new_context();
+    if let Some(event_loop) = ctx.event_loop() {
+        event_loop.watch_loaded_files(WATCH_INTERVAL);
+        event_loop.record_loaded_file(file);
+    }
+    let mut args = rest;
+    args.insert(0, file.to_string());
+    init_globals(&mut ctx, args);
+
+    let result = match resolver::import(file).map(String::from_utf8) {
+        Ok(Ok(code)) => {
+            set_filename(&mut ctx, file);
+            ctx.eval_module_str(code, file).map(|_| ())
+        }
+        Ok(Err(_)) => Err(JsError::new(
+            JsErrorKind::Exception,
+            &format!("invalid format: {}", file),
+        )),
+        Err(e) => Err(JsError::new(JsErrorKind::Exception, &format!("{}", e))),
+    };
+    // the watch timer keeps the loop busy, so it only ends with an error
+    let e = match result {
+        Ok(()) => loop {
+            if let Err(e) = ctx.js_loop() {
+                break e;
+            }
+        },
+        Err(e) => e,
+    };
+    let event_loop = ctx.event_loop()?;
+    match e.kind {
+        JsErrorKind::WatchedFileChanged => return None,
+        JsErrorKind::Interrupted => match event_loop.take_exit_code() {
+            Some(code) => eprintln!("'{}' exited with code {}", file, code),
+            None => eprintln!("{}", e),
+        },
+        JsErrorKind::Exception => eprintln!("{:#}", e),
+    }
+    event_loop.take_watched_files()
+}
+
+/// `--print` needs the completion value of the code, which only scripts have,
+/// so the transpiled code is evaluated in the global scope instead.
+fn run_print(ctx: &mut Context, code: String) -> i32 {
+    let val = match ctx.eval_global_str(code) {
+        Ok(val) => val,
+        Err(e) => return exit_on_error(ctx, e),
+    };
+    if let JsValue::Object(mut console) = ctx.get_global().get("console") {
+        console.invoke("log", &[val]);
+    }
+    run_to_exit(ctx)
+}
+
+/// Inline sources go through the same SWC pipeline `resolver::import` uses for
+/// files, so TypeScript and JSX work in every mode.
+fn transpile_inline(filename: &str, source: &str) -> String {
+    match tsx_to_js_str(Some(filename), source, &OutputType::ESModule) {
+        Ok(code) => code,
+        // diagnostics were already printed by the transpiler
+        Err(_) => std::process::exit(1),
+    }
+}
+
+/// Reports every parse diagnostic in `files` as `file:line:col` and returns 1
+/// if any of them failed to parse.
+fn run_check(files: Vec<String>) -> i32 {
+    let mut failed = false;
+    for file in files {
+        let source = match std::fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                failed = true;
+                continue;
+            }
+        };
+        for d in transpiler::check_syntax(Some(&file), &source) {
+            eprintln!("{}:{}:{}: error: {}", file, d.line, d.col, d.message);
+            failed = true;
+        }
+    }
+    if failed {
+        1
+    } else {
+        0
+    }
 }
 
-static mut RT: Lazy<Mutex<Runtime>> = Lazy::new(|| {
-    let rt = Runtime::new();
-    Mutex::new(rt)
-});
+/// `drop compile <entry> [-o <output>]` writes the bytecode of `entry` and all
+/// the modules it imports to `output`, which defaults to `entry.qjsbc`.
+fn run_compile(args: Vec<String>) -> i32 {
+    use argparse::ArgumentParser;
+    let mut entry = String::new();
+    let mut output: Option<String> = None;
+    {
+        let mut arg_parser = ArgumentParser::new();
+        arg_parser.set_description("compile a script and its imports to QuickJS bytecode");
+        arg_parser.refer(&mut entry).required().add_argument(
+            "entry",
+            argparse::Store,
+            "script to compile",
+        );
+        arg_parser.refer(&mut output).add_option(
+            &["-o", "--output"],
+            argparse::StoreOption,
+            "output file, defaults to the entry with a .qjsbc extension",
+        );
+        if let Err(code) = arg_parser.parse(args, &mut std::io::stdout(), &mut std::io::stderr()) {
+            return code;
+        }
+    }
+    let output = output.unwrap_or_else(|| {
+        std::path::Path::new(&entry)
+            .with_extension("qjsbc")
+            .to_string_lossy()
+            .to_string()
+    });
+
+    let mut rt = Runtime::new();
+    let mut ctx = rt.new_context();
+    let buf = match ctx.compile_module_graph(&entry) {
+        Ok(buf) => buf,
+        Err(e) => {
+            eprintln!("{}: {}", entry, e);
+            return 1;
+        }
+    };
+    match std::fs::write(&output, buf) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("{}: {}", output, e);
+            1
+        }
+    }
+}
 
-static mut CTX: Lazy<Mutex<Context>> = Lazy::new(|| unsafe {
-    let mut rt = RT.lock().unwrap();
-    let ctx = rt.new_context();
-    Mutex::new(ctx)
-});
+/// `drop bundle <entry> [-o <output>] [--format esm|iife]` writes `entry` and
+/// every local module it imports as a single script, to stdout by default.
+fn run_bundle(args: Vec<String>) -> i32 {
+    use argparse::ArgumentParser;
+    let mut entry = String::new();
+    let mut output: Option<String> = None;
+    let mut format = "esm".to_string();
+    {
+        let mut arg_parser = ArgumentParser::new();
+        arg_parser.set_description("bundle a script and its imports into a single file");
+        arg_parser.refer(&mut entry).required().add_argument(
+            "entry",
+            argparse::Store,
+            "script to bundle",
+        );
+        arg_parser.refer(&mut output).add_option(
+            &["-o", "--output"],
+            argparse::StoreOption,
+            "output file, defaults to stdout",
+        );
+        arg_parser.refer(&mut format).add_option(
+            &["-f", "--format"],
+            argparse::Store,
+            "esm (default) or iife",
+        );
+        if let Err(code) = arg_parser.parse(args, &mut std::io::stdout(), &mut std::io::stderr()) {
+            return code;
+        }
+    }
+    let format = match format.as_str() {
+        "esm" => BundleFormat::ESModule,
+        "iife" => BundleFormat::Iife,
+        _ => {
+            eprintln!("unknown bundle format: {}", format);
+            return 2;
+        }
+    };
 
-extern "C" fn exit() {
-    unsafe {
-        let mut ctx = CTX.lock().unwrap();
-        ctx.js_loop().unwrap();
-        // TODO: this needs to check for errors and propagate the exit code
-        ctx.eval_global_str("import('process').then(p=>p.emit('beforeExit'))".into());
-        ctx.js_loop().unwrap();
+    let code = match bundle(&entry, &format) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let written = match &output {
+        Some(output) => std::fs::write(output, code),
+        None => std::io::Write::write_all(&mut std::io::stdout(), code.as_bytes()),
+    };
+    match written {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("{}: {}", output.unwrap_or_default(), e);
+            1
+        }
     }
 }
 
+/// Runs `compile` or `bundle` when it is the very first argument. Anywhere
+/// else, e.g. after an option, it is the script to run, and `drop ./compile`
+/// runs a script with that name from the working directory.
+fn run_subcommand() -> Option<i32> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let run: fn(Vec<String>) -> i32 = match args.first().map(String::as_str) {
+        Some("compile") => run_compile,
+        Some("bundle") => run_bundle,
+        _ => return None,
+    };
+    args[0] = format!("drop {}", args[0]);
+    Some(run(args))
+}
+
 fn main() {
-    unsafe {
-        let mut ctx = CTX.lock().unwrap();
-        let (file_path, mut rest_arg) = args_parse();
-        let entrypoint =
-            resolver::import(&file_path).expect(format!("file not found: {}", &file_path).as_str());
-        let code = String::from_utf8(entrypoint)
-            .expect(format!("invalid format: {}", &file_path).as_str());
-        rest_arg.insert(0, file_path.clone());
-        ctx.put_args(rest_arg);
-        ctx.eval_global_str(include_str!("./main.js").into());
-        ctx.promise_loop_poll();
-        ctx.eval_global_str(format!("globalThis.__filename = '{}'", &file_path).into());
-        ctx.eval_module_str(code, &file_path);
-        ctx.js_loop().unwrap();
-        libc::atexit(exit);
+    if let Some(exit_code) = run_subcommand() {
+        std::process::exit(exit_code)
+    }
+
+    let Args {
+        limits,
+        check,
+        watch,
+        file,
+        eval,
+        print,
+        mut rest,
+    } = args_parse();
+
+    if check {
+        if !file.is_empty() {
+            rest.insert(0, file);
+        }
+        std::process::exit(run_check(rest))
     }
+
+    if watch {
+        if file.is_empty() || file == "-" || eval.is_some() || print.is_some() {
+            eprintln!("--watch requires a script file");
+            std::process::exit(9)
+        }
+        run_watch(&file, rest, limits)
+    }
+
+    let mut rt = limits.new_runtime();
+    let mut ctx = rt.new_context();
+
+    let exit_code = if eval.is_some() || print.is_some() {
+        if !file.is_empty() {
+            rest.insert(0, file);
+        }
+        init_globals(&mut ctx, rest);
+        if let Some(source) = eval {
+            let code = transpile_inline("[eval]", &source);
+            run_module(&mut ctx, "[eval]", code)
+        } else {
+            let code = transpile_inline("[eval]", &print.unwrap_or_default());
+            run_print(&mut ctx, code)
+        }
+    } else if file.is_empty() {
+        init_globals(&mut ctx, rest);
+        Repl::new(&mut ctx).run()
+    } else if file == "-" {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .expect("invalid format: [stdin]");
+        init_globals(&mut ctx, rest);
+        let code = transpile_inline("[stdin]", &source);
+        run_module(&mut ctx, "[stdin]", code)
+    } else if js_bytecode::is_bytecode_file(&file) {
+        let buf = std::fs::read(&file).expect(format!("file not found: {}", &file).as_str());
+        rest.insert(0, file.clone());
+        init_globals(&mut ctx, rest);
+        run_bytecode(&mut ctx, &file, &buf)
+    } else {
+        let entrypoint =
+            resolver::import(&file).expect(format!("file not found: {}", &file).as_str());
+        let code =
+            String::from_utf8(entrypoint).expect(format!("invalid format: {}", &file).as_str());
+        rest.insert(0, file.clone());
+        init_globals(&mut ctx, rest);
+        run_module(&mut ctx, &file, code)
+    };
+    std::process::exit(exit_code)
 }
@@ -22,13 +22,13 @@ export class TextEncoder {
 		if (isUndefined(arr)) {
 			return new Uint8Array();
 		} else {
-			return new Uint8Array(arr);
+			return arr;
 		}
 	}
 
 	encodeInto(src, dest) {
 		if (dest instanceof Uint8Array) {
-			return text_encode_into(src, "utf8", dest.buffer, dest.byteOffset);
+			return text_encode_into(src, "utf8", dest);
 		} else {
 			throw new TypeError('The "dest" argument must be an instance of Uint8Array.');
 		}
@@ -103,9 +103,7 @@ export class TextDecoder {
 	decode(input) {
 		if (typeof input !== "undefined") {
 			let ret;
-			if (input.buffer instanceof ArrayBuffer) {
-				ret = text_decode(input.buffer, this.encoding, this.fatal);
-			} else if (input instanceof ArrayBuffer) {
+			if (ArrayBuffer.isView(input) || input instanceof ArrayBuffer) {
 				ret = text_decode(input, this.encoding, this.fatal);
 			}
 			if (isError(ret)) {
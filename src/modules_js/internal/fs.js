@@ -123,9 +123,9 @@ function applyDefaultValueAlsoNull(dest, def) {
  * @property {number} size
  * @property {number | null} blksize
  * @property {number | null} blocks
- * @property {number | null} atime
- * @property {number | null} mtime
- * @property {number | null} birthtime
+ * @property {Date} atime
+ * @property {Date} mtime
+ * @property {Date} birthtime
  */
 
 class Stats {
@@ -142,14 +142,14 @@ class Stats {
 		this.size = origin.size || 0;
 		this.blksize = origin.blksize;
 		this.blocks = origin.blocks;
-		this.mtime = new Date(origin.mtime);
-		this.atime = new Date(origin.atime);
-		this.birthtime = new Date(origin.birthtime);
-		this.mtimeMs = origin.mtime;
-		this.atimeMs = origin.atime;
-		this.birthtimeMs = origin.birthtime;
-		this.ctime = new Date(origin.mtime);
-		this.ctimeMs = origin.mtime;
+		this.mtime = origin.mtime;
+		this.atime = origin.atime;
+		this.birthtime = origin.birthtime;
+		this.mtimeMs = origin.mtime.getTime();
+		this.atimeMs = origin.atime.getTime();
+		this.birthtimeMs = origin.birthtime.getTime();
+		this.ctime = new Date(this.mtimeMs);
+		this.ctimeMs = this.mtimeMs;
 		this.#origin = origin;
 	}
 	isFile() {
@@ -199,18 +199,18 @@ class BigIntStats {
 		this.size = toBigInt(origin.size) || 0n;
 		this.blksize = toBigInt(origin.blksize);
 		this.blocks = toBigInt(origin.blocks);
-		this.mtime = new Date(origin.mtime);
-		this.atime = new Date(origin.atime);
-		this.birthtime = new Date(origin.birthtime);
-		this.mtimeMs = toBigInt(origin.mtime);
-		this.atimeMs = toBigInt(origin.atime);
-		this.birthtimeMs = toBigInt(origin.birthtime);
-		this.mtimeNs = toBigInt(origin.mtime) * 1000000n;
-		this.atimeNs = toBigInt(origin.atime) * 1000000n;
-		this.birthtimeNs = toBigInt(origin.birthtime) * 1000000n;
-		this.ctime = new Date(origin.mtime);
-		this.ctimeMs = toBigInt(origin.mtime);
-		this.ctimeNs = toBigInt(origin.mtime) * 1000000n;
+		this.mtime = origin.mtime;
+		this.atime = origin.atime;
+		this.birthtime = origin.birthtime;
+		this.mtimeMs = toBigInt(origin.mtime.getTime());
+		this.atimeMs = toBigInt(origin.atime.getTime());
+		this.birthtimeMs = toBigInt(origin.birthtime.getTime());
+		this.mtimeNs = origin.mtime_ns ?? this.mtimeMs * 1000000n;
+		this.atimeNs = origin.atime_ns ?? this.atimeMs * 1000000n;
+		this.birthtimeNs = origin.birthtime_ns ?? this.birthtimeMs * 1000000n;
+		this.ctime = new Date(origin.mtime.getTime());
+		this.ctimeMs = this.mtimeMs;
+		this.ctimeNs = this.mtimeNs;
 		this.#origin = origin;
 	}
 	isFile() {
@@ -465,7 +465,7 @@ function statSync(path, options = { bigint: false, throwIfNoEntry: true }) {
 	options = applyDefaultValue(options, { bigint: false, throwIfNoEntry: true });
 
 	try {
-		let stat = binding.statSync(path);
+		let stat = binding.statSync(path, options.bigint === true);
 		if (options.bigint === true) {
 			return new BigIntStats(stat);
 		} else {
@@ -502,7 +502,7 @@ function lstatSync(path, options = { bigint: false, throwIfNoEntry: true }) {
 	options = applyDefaultValue(options, { bigint: false, throwIfNoEntry: true });
 
 	try {
-		let stat = binding.lstatSync(path);
+		let stat = binding.lstatSync(path, options.bigint === true);
 		if (options.bigint === true) {
 			return new BigIntStats(stat);
 		} else {
@@ -538,7 +538,7 @@ function fstatSync(fd, options = { bigint: false, throwIfNoEntry: true }) {
 	options = applyDefaultValue(options, { bigint: false, throwIfNoEntry: true });
 
 	try {
-		let stat = binding.fstatSync(fd);
+		let stat = binding.fstatSync(fd, options.bigint === true);
 		if (options.bigint === true) {
 			return new BigIntStats(stat);
 		} else {
@@ -782,13 +782,6 @@ function utimesSync(path, atime, mtime) {
 	path = getValidatedPath(path);
 	atime = getValidTime(atime);
 	mtime = getValidTime(mtime);
-	if (atime instanceof Date) {
-		atime = atime.getTime();
-	}
-	mtime = getValidTime(mtime);
-	if (mtime instanceof Date) {
-		mtime = mtime.getTime();
-	}
 
 	try {
 		binding.utimeSync(path, atime, mtime);
@@ -1953,7 +1946,7 @@ function write(fd, buffer, offset, length, position, callback) {
 	position = position ?? -1;
 
 	if (isArrayBufferView(buffer) && !(buffer instanceof Buffer)) {
-		buffer = Buffer.from(buffer.buffer);
+		buffer = Buffer.from(buffer.buffer, buffer.byteOffset, buffer.byteLength);
 	}
 
 	if (typeof buffer !== "string" && !(buffer instanceof Buffer)) {
@@ -1967,7 +1960,7 @@ function write(fd, buffer, offset, length, position, callback) {
 	validateInteger(fd, "fd");
 	validateInteger(offset + length, "length + offset", 0, buffer.byteLength);
 
-	fwrite(fd, position, buffer.buffer.slice(offset, offset + length))
+	fwrite(fd, position, buffer.subarray(offset, offset + length))
 		.then((len) => {
 			callback(null, len, buffer);
 		})
@@ -2018,7 +2011,7 @@ function writeSync(fd, buffer, offset, length, position) {
 	validateInteger(length + offset, "length + offset", 0, buffer.byteLength);
 
 	try {
-		let len = binding.fwriteSync(fd, position, buffer.buffer.slice(offset, offset + length));
+		let len = binding.fwriteSync(fd, position, buffer.subarray(offset, offset + length));
 		return len;
 	} catch (e) {
 		throw wasiFsSyscallErrorMap(e, "write");
@@ -2300,7 +2293,7 @@ function writev(fd, buffer, position, callback) {
 		length += buf.byteLength;
 	}
 
-	fwrite(fd, position, Buffer.concat(buffer).buffer)
+	fwrite(fd, position, Buffer.concat(buffer))
 		.then((len) => {
 			callback(null, len, buffer);
 		})
@@ -2327,7 +2320,7 @@ function writevSync(fd, buffer, position = -1) {
 	}
 
 	try {
-		let len = binding.fwriteSync(fd, position, Buffer.concat(buffer).buffer);
+		let len = binding.fwriteSync(fd, position, Buffer.concat(buffer));
 		return len;
 	} catch (err) {
 		throw wasiFsSyscallErrorMap(err, "write");
@@ -2374,10 +2367,10 @@ class Dir {
 		this.path = path;
 	}
 
+	#entries = null;
 	#dataBuf = [];
 	#idx = 0;
 	#fin = false;
-	#cookie = 0;
 	#closed = false;
 
 	#fetch() {
@@ -2386,10 +2379,19 @@ class Dir {
 		}
 		if (this.#idx === this.#dataBuf.length && !this.#fin) {
 			try {
-				let data = binding.freaddirSync(this.#fd, this.#cookie);
-				this.#dataBuf.push(...data.res.filter((d) => d.name !== "." && d.name !== ".."));
-				this.#fin = data.fin;
-				this.#cookie = data.cookie;
+				// entries are read from the directory as they are pulled
+				this.#entries ??= binding.freaddirSync(this.#fd);
+				for (;;) {
+					let { done, value } = this.#entries.next();
+					if (done) {
+						this.#fin = true;
+						break;
+					}
+					if (value.name !== "." && value.name !== "..") {
+						this.#dataBuf.push(value);
+						break;
+					}
+				}
 			} catch (err) {
 				let e = new Error(err.message);
 				e.code = err.code;
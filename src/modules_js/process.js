@@ -52,6 +52,7 @@ function _linkedBinding(name) {
 }
 var domain = {};
 var _exiting = false;
+var exitCode = undefined;
 var config = {};
 function dlopen(name) {
 	unimplemented("dlopen");
@@ -185,12 +186,12 @@ function hrtime(previousTimestamp) {
 	return [seconds, nanoseconds];
 }
 
-hrtime.bigint = function (time) {
-	var diff = hrtime(time);
-	if (typeof BigInt === "undefined") {
-		return diff[0] * nanoPerSec + diff[1];
+hrtime.bigint = function () {
+	if (typeof globalThis._hrtimeBigint === "function") {
+		return globalThis._hrtimeBigint();
 	}
-	return BigInt(diff[0] * nanoPerSec) + BigInt(diff[1]);
+	var time = hrtime();
+	return BigInt(time[0] * nanoPerSec) + BigInt(time[1]);
 };
 
 var _maxListeners = 10;
@@ -240,6 +241,7 @@ var process = {
 	listeners: listeners,
 	domain: domain,
 	_exiting: _exiting,
+	exitCode: exitCode,
 	config: config,
 	dlopen: dlopen,
 	uptime: uptime,
@@ -0,0 +1,24 @@
+import { Buffer } from "buffer";
+import { serialize as _serialize, deserialize as _deserialize } from "_node:v8";
+import { ERR_INVALID_ARG_TYPE } from "internal/errors";
+import { isArrayBufferView } from "internal/util/types";
+
+// The native side writes the wire format of V8, the same one
+// `structuredClone` goes through.
+function serialize(value) {
+	return Buffer.from(_serialize(value));
+}
+
+function deserialize(buffer) {
+	if (!isArrayBufferView(buffer)) {
+		throw new ERR_INVALID_ARG_TYPE("buffer", ["Buffer", "TypedArray", "DataView"], buffer);
+	}
+	return _deserialize(buffer);
+}
+
+export { serialize, deserialize };
+
+export default {
+	serialize,
+	deserialize,
+};
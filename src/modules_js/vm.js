@@ -0,0 +1,112 @@
+import { VmContext } from "_node:vm";
+
+// Every contextified object owns a separate QuickJS context. QuickJS can't
+// swap out the global object of a context, so the properties of the sandbox
+// are copied onto the global before each run and copied back afterwards.
+const kContext = Symbol("vm.context");
+
+function validateString(value, name) {
+	if (typeof value !== "string") {
+		throw new TypeError(`The "${name}" argument must be of type string`);
+	}
+}
+
+function getFilename(options, fallback) {
+	if (typeof options === "string") {
+		return options;
+	}
+	return options?.filename ?? fallback;
+}
+
+function isContext(contextObject) {
+	return contextObject != null && Object.prototype.hasOwnProperty.call(contextObject, kContext);
+}
+
+function createContext(contextObject = {}) {
+	if (isContext(contextObject)) {
+		return contextObject;
+	}
+	if (typeof contextObject !== "object" || contextObject === null) {
+		throw new TypeError('The "contextObject" argument must be of type object');
+	}
+	const handle = new VmContext();
+	const global = handle.global();
+	Object.defineProperty(contextObject, kContext, {
+		value: {
+			handle,
+			global,
+			builtins: new Set(Object.getOwnPropertyNames(global)),
+			synced: new Set(),
+		},
+	});
+	return contextObject;
+}
+
+function runInContext(code, contextObject, options) {
+	validateString(code, "code");
+	if (!isContext(contextObject)) {
+		throw new TypeError('The "contextObject" argument must be a vm.Context');
+	}
+	const context = contextObject[kContext];
+	const { handle, global, builtins, synced } = context;
+
+	for (const key of synced) {
+		if (!(key in contextObject)) {
+			delete global[key];
+		}
+	}
+	for (const key of Object.keys(contextObject)) {
+		global[key] = contextObject[key];
+	}
+	try {
+		return handle.runScript(code, getFilename(options, "evalmachine.<anonymous>"));
+	} finally {
+		context.synced = new Set();
+		for (const key of Object.getOwnPropertyNames(global)) {
+			if (!builtins.has(key)) {
+				contextObject[key] = global[key];
+				context.synced.add(key);
+			}
+		}
+	}
+}
+
+function runInNewContext(code, contextObject, options) {
+	return runInContext(code, createContext(contextObject), options);
+}
+
+function runInThisContext(code) {
+	validateString(code, "code");
+	return (0, eval)(code);
+}
+
+class Script {
+	constructor(code, options) {
+		validateString(code, "code");
+		this.code = code;
+		this.filename = getFilename(options, "evalmachine.<anonymous>");
+	}
+
+	runInContext(contextObject) {
+		return runInContext(this.code, contextObject, this.filename);
+	}
+
+	runInNewContext(contextObject) {
+		return runInNewContext(this.code, contextObject, this.filename);
+	}
+
+	runInThisContext() {
+		return runInThisContext(this.code);
+	}
+}
+
+export { Script, createContext, isContext, runInContext, runInNewContext, runInThisContext };
+
+export default {
+	Script,
+	createContext,
+	isContext,
+	runInContext,
+	runInNewContext,
+	runInThisContext,
+};
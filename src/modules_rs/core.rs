@@ -1,34 +1,30 @@
+use crate::event_loop::check_callback_result;
 use crate::quickjs_sys::*;
 use std::string::FromUtf8Error;
 
-fn set_timeout(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
-    let callback = argv.get(0);
-    let timeout = argv.get(1);
-    let rest_args = argv.get(2..).map(|args| args.to_vec());
-    if let (Some(JsValue::Function(callback)), Some(JsValue::Int(timeout)), Some(event_loop)) =
-        (callback, timeout, ctx.event_loop())
-    {
-        let n = event_loop.set_timeout(
-            callback.clone(),
-            std::time::Duration::from_millis((*timeout) as u64),
-            rest_args,
-        );
-        JsValue::Int(n as i32)
-    } else {
-        JsValue::UnDefined
-    }
+fn set_timeout(
+    ctx: &mut Context,
+    (callback, timeout, Rest(args)): (JsFunction, JsValue, Rest<JsValue>),
+) -> Result<Option<usize>, JsException> {
+    // like Node, `setTimeout(fn, "10")` waits 10ms and NaN doesn't wait
+    let timeout = ctx.value_to_number(&timeout)?.max(0.0) as u64;
+    let event_loop = match ctx.event_loop() {
+        Some(event_loop) => event_loop,
+        None => return Ok(None),
+    };
+    Ok(Some(event_loop.set_timeout(
+        callback,
+        std::time::Duration::from_millis(timeout),
+        Some(args),
+    )))
 }
 
-fn set_immediate(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
-    let callback = argv.get(0);
-    let args = argv.get(1..).map(|v| v.to_vec());
-    if let (Some(JsValue::Function(callback)), Some(event_loop)) = (callback, ctx.event_loop()) {
-        let callback = callback.clone();
-        let n = event_loop.set_timeout(callback, std::time::Duration::from_secs(0), args);
-        JsValue::Int(n as i32)
-    } else {
-        JsValue::UnDefined
-    }
+fn set_immediate(
+    ctx: &mut Context,
+    (callback, Rest(args)): (JsFunction, Rest<JsValue>),
+) -> Option<usize> {
+    let event_loop = ctx.event_loop()?;
+    Some(event_loop.set_timeout(callback, std::time::Duration::from_secs(0), Some(args)))
 }
 
 fn next_tick(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
@@ -36,26 +32,70 @@ fn next_tick(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue
     let args = argv.get(1..).map(|v| v.to_vec());
     if let (Some(JsValue::Function(callback)), Some(event_loop)) = (callback, ctx.event_loop()) {
         let callback = callback.clone();
-        event_loop.set_next_tick(Box::new(move |_ctx| {
-            match args {
+        event_loop.set_next_tick(Box::new(move |ctx| {
+            let res = match args {
                 Some(args) => callback.call(&args),
                 None => callback.call(&[]),
             };
+            check_callback_result(ctx, res);
         }));
     }
     JsValue::UnDefined
 }
 
-fn os_exit(_ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
-    let code = if let Some(JsValue::Int(c)) = argv.get(0) {
-        *c
-    } else {
-        0
+fn os_exit(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    let code = match argv.get(0) {
+        Some(JsValue::Int(c)) => Some(*c),
+        Some(JsValue::Float(c)) => Some(*c as i32),
+        _ => None,
     };
 
+    let code = ctx.emit_exit(code);
+    if let Some(event_loop) = ctx.event_loop() {
+        // under `--watch` only the current run ends
+        if event_loop.is_watching() {
+            event_loop.exit_run(code);
+            return JsValue::Exception(ctx.throw_uncatchable_error("process.exit() called"));
+        }
+    }
     std::process::exit(code)
 }
 
+fn structured_clone(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    let value = match argv.get(0) {
+        Some(value) => value,
+        None => {
+            let e = ctx.throw_type_error("The \"value\" argument must be specified");
+            return JsValue::Exception(e);
+        }
+    };
+    let transfer = match argv.get(1) {
+        None | Some(JsValue::UnDefined) | Some(JsValue::Null) => vec![],
+        Some(JsValue::Object(options)) => match options.get("transfer") {
+            JsValue::UnDefined => vec![],
+            JsValue::Array(transfer) => match transfer.to_vec() {
+                Ok(transfer) => transfer,
+                Err(e) => return JsValue::Exception(e),
+            },
+            JsValue::Exception(e) => return JsValue::Exception(e),
+            _ => return JsValue::Exception(ctx.throw_type_error("transfer must be an array")),
+        },
+        Some(_) => return JsValue::Exception(ctx.throw_type_error("options must be an object")),
+    };
+    let mut buffers = vec![];
+    for (i, item) in transfer.into_iter().enumerate() {
+        match item {
+            JsValue::ArrayBuffer(buffer) => buffers.push(buffer),
+            _ => {
+                let msg = format!("value at index {} of transfer is not transferable", i);
+                return JsValue::Exception(ctx.throw_data_clone_error(&msg));
+            }
+        }
+    }
+    ctx.structured_clone(value, &buffers)
+        .unwrap_or_else(JsValue::Exception)
+}
+
 struct ClearTimeout;
 impl JsFn for ClearTimeout {
     fn call(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
@@ -67,6 +107,14 @@ impl JsFn for ClearTimeout {
     }
 }
 
+/// Nanoseconds of the monotonic clock, for `process.hrtime.bigint()`.
+fn hrtime_bigint(ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    match unsafe { wasi::clock_time_get(wasi::CLOCKID_MONOTONIC, 1) } {
+        Ok(ns) => ctx.new_bigint_u64(ns).into(),
+        Err(e) => JsValue::Exception(ctx.throw_internal_type_error(e.message())),
+    }
+}
+
 pub fn init_ext_function(_ctx: &mut Context) {}
 
 pub fn init_global_function(ctx: &mut Context) {
@@ -77,15 +125,25 @@ pub fn init_global_function(ctx: &mut Context) {
     );
     global.set(
         "setTimeout",
-        ctx.wrap_function("setTimeout", set_timeout).into(),
+        ctx.wrap_typed_function("setTimeout", set_timeout).into(),
     );
     global.set(
         "setImmediate",
-        ctx.wrap_function("setImmediate", set_immediate).into(),
+        ctx.wrap_typed_function("setImmediate", set_immediate)
+            .into(),
     );
     global.set("nextTick", ctx.wrap_function("nextTick", next_tick).into());
     global.set("exit", ctx.wrap_function("exit", os_exit).into());
     global.set("env", env_object(ctx).into());
+    global.set(
+        "_hrtimeBigint",
+        ctx.wrap_function("_hrtimeBigint", hrtime_bigint).into(),
+    );
+    global.set(
+        "structuredClone",
+        ctx.wrap_function("structuredClone", structured_clone)
+            .into(),
+    );
 }
 
 fn env_object(ctx: &mut Context) -> JsObject {
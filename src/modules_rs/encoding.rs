@@ -23,7 +23,7 @@ fn text_encode(ctx: &mut Context, _: JsValue, params: &[JsValue]) -> JsValue {
             "" | "utf8" | "utf-8" => {
                 let b = UTF_8.encode(s.as_str(), EncoderTrap::Replace);
                 match b {
-                    Ok(ret) => ctx.new_array_buffer(&ret).into(),
+                    Ok(ret) => ctx.new_uint8_array(&ret).into(),
                     Err(e) => {
                         ctx.throw_type_error(&e);
                         JsValue::UnDefined
@@ -85,15 +85,15 @@ fn text_encode_into(ctx: &mut Context, _: JsValue, params: &[JsValue]) -> JsValu
     if src.is_none() || dest.is_none() {
         return JsValue::UnDefined;
     }
-    if let (
-        JsValue::String(s),
-        Some(JsValue::ArrayBuffer(ref mut dst_buff)),
-        Some(JsValue::Int(offset)),
-    ) = (ctx.value_to_string(src.unwrap()), dest.cloned(), offset)
+    if let (JsValue::String(s), Some(JsValue::TypedArray(ref mut dst_array))) =
+        (ctx.value_to_string(src.unwrap()), dest.cloned())
     {
         let src = s.as_str();
-        let dst = dst_buff.as_mut();
-        let offset = dst.len().min(*offset as usize);
+        let dst = dst_array.as_mut();
+        let offset = match offset {
+            Some(JsValue::Int(offset)) => dst.len().min(*offset as usize),
+            _ => 0,
+        };
 
         match utf_label {
             "" | "utf8" | "utf-8" => {
@@ -149,7 +149,7 @@ fn text_decode(ctx: &mut Context, _: JsValue, params: &[JsValue]) -> JsValue {
         }
     }
 
-    if let JsValue::ArrayBuffer(s) = s.unwrap() {
+    if let Some(s) = s.unwrap().as_bytes() {
         match utf_label {
             "" | "utf8" | "utf-8" => {
                 let b = UTF_8.decode(s.as_ref(), trap);
@@ -1,12 +1,12 @@
 use wasi::Fd;
 
-use crate::event_loop::PollResult;
 use crate::quickjs_sys::*;
 use std::convert::TryInto;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::fs;
 use std::fs::Permissions;
+use std::future::Future;
 use std::io;
 use std::os::wasi::prelude::FromRawFd;
 use std::ptr;
@@ -106,7 +106,9 @@ fn permissions_to_mode(permit: Permissions) -> i32 {
     p | p << 3 | p << 6
 }
 
-fn stat_to_js_object(ctx: &mut Context, stat: wasi::Filestat) -> JsValue {
+/// With `bigint` the sizes and ids are `BigInt`s, and the times are also
+/// given in nanoseconds, like node's `{ bigint: true }` option.
+fn stat_to_js_object(ctx: &mut Context, stat: wasi::Filestat, bigint: bool) -> JsValue {
     let mut res = ctx.new_object();
     res.set(
         "is_file",
@@ -135,9 +137,12 @@ fn stat_to_js_object(ctx: &mut Context, stat: wasi::Filestat) -> JsValue {
             .into(),
     );
     res.set("size", stat.size.into());
-    res.set("mtime", (stat.mtim / 1000000).into());
-    res.set("atime", (stat.atim / 1000000).into());
-    res.set("birthtime", (stat.ctim / 1000000).into());
+    res.set("mtime", ctx.new_date((stat.mtim / 1000000) as f64).into());
+    res.set("atime", ctx.new_date((stat.atim / 1000000) as f64).into());
+    res.set(
+        "birthtime",
+        ctx.new_date((stat.ctim / 1000000) as f64).into(),
+    );
     res.set("dev", stat.dev.into());
     res.set("ino", stat.ino.into());
     res.set("mode", 0o666.into());
@@ -147,6 +152,15 @@ fn stat_to_js_object(ctx: &mut Context, stat: wasi::Filestat) -> JsValue {
     res.set("rdev", 0.into());
     res.set("blksize", 0.into());
     res.set("blocks", 0.into());
+    if bigint {
+        res.set("size", ctx.new_bigint_u64(stat.size).into());
+        res.set("dev", ctx.new_bigint_u64(stat.dev).into());
+        res.set("ino", ctx.new_bigint_u64(stat.ino).into());
+        res.set("nlink", ctx.new_bigint_u64(stat.nlink).into());
+        res.set("mtime_ns", ctx.new_bigint_u64(stat.mtim).into());
+        res.set("atime_ns", ctx.new_bigint_u64(stat.atim).into());
+        res.set("birthtime_ns", ctx.new_bigint_u64(stat.ctim).into());
+    }
     JsValue::Object(res)
 }
 
@@ -165,6 +179,7 @@ fn errno_to_js_object(ctx: &mut Context, e: wasi::Errno) -> JsValue {
 
 fn stat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     let path = arg.get(0);
+    let bigint = matches!(arg.get(1), Some(JsValue::Bool(true)));
     if path.is_none() {
         return JsValue::UnDefined;
     }
@@ -181,7 +196,7 @@ fn stat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
         return match unsafe {
             wasi::path_filestat_get(dir, wasi::LOOKUPFLAGS_SYMLINK_FOLLOW, file.as_str())
         } {
-            Ok(stat) => stat_to_js_object(ctx, stat),
+            Ok(stat) => stat_to_js_object(ctx, stat, bigint),
             Err(e) => {
                 let err = errno_to_js_object(ctx, e);
                 JsValue::Exception(ctx.throw_error(err))
@@ -194,12 +209,13 @@ fn stat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
 
 fn fstat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     let fd = arg.get(0);
+    let bigint = matches!(arg.get(1), Some(JsValue::Bool(true)));
     if fd.is_none() {
         return JsValue::UnDefined;
     }
     if let Some(f) = get_js_number(fd) {
         return match unsafe { wasi::fd_filestat_get(f as u32) } {
-            Ok(stat) => stat_to_js_object(ctx, stat),
+            Ok(stat) => stat_to_js_object(ctx, stat, bigint),
             Err(e) => {
                 let err = errno_to_js_object(ctx, e);
                 JsValue::Exception(ctx.throw_error(err))
@@ -212,6 +228,7 @@ fn fstat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
 
 fn lstat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     let path = arg.get(0);
+    let bigint = matches!(arg.get(1), Some(JsValue::Bool(true)));
     if path.is_none() {
         return JsValue::UnDefined;
     }
@@ -226,7 +243,7 @@ fn lstat_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
             }
         };
         return match unsafe { wasi::path_filestat_get(dir, 0, file.as_str()) } {
-            Ok(stat) => stat_to_js_object(ctx, stat),
+            Ok(stat) => stat_to_js_object(ctx, stat, bigint),
             Err(e) => {
                 let err = errno_to_js_object(ctx, e);
                 JsValue::Exception(ctx.throw_error(err))
@@ -503,8 +520,8 @@ fn utime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
         return JsValue::UnDefined;
     }
     if let Some(JsValue::String(p)) = path {
-        if let Some(a) = get_js_number(atime) {
-            if let Some(m) = get_js_number(mtime) {
+        if let Some(a) = get_js_time_ns(atime) {
+            if let Some(m) = get_js_time_ns(mtime) {
                 let (dir, file) = match open_parent(p.as_str()) {
                     Ok(ok) => ok,
                     Err(e) => {
@@ -519,8 +536,8 @@ fn utime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
                         dir,
                         wasi::LOOKUPFLAGS_SYMLINK_FOLLOW,
                         file.as_str(),
-                        (a as u64) * 1000000,
-                        (m as u64) * 1000000,
+                        a,
+                        m,
                         wasi::FSTFLAGS_ATIM | wasi::FSTFLAGS_MTIM,
                     )
                 };
@@ -545,8 +562,8 @@ fn lutime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValu
         return JsValue::UnDefined;
     }
     if let Some(JsValue::String(p)) = path {
-        if let Some(a) = get_js_number(atime) {
-            if let Some(m) = get_js_number(mtime) {
+        if let Some(a) = get_js_time_ns(atime) {
+            if let Some(m) = get_js_time_ns(mtime) {
                 let (dir, file) = match open_parent(p.as_str()) {
                     Ok(ok) => ok,
                     Err(e) => {
@@ -561,8 +578,8 @@ fn lutime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValu
                         dir,
                         0,
                         file.as_str(),
-                        (a as u64) * 1000000,
-                        (m as u64) * 1000000,
+                        a,
+                        m,
                         wasi::FSTFLAGS_ATIM | wasi::FSTFLAGS_MTIM,
                     )
                 };
@@ -587,13 +604,13 @@ fn futime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValu
         return JsValue::UnDefined;
     }
     if let Some(JsValue::Int(f)) = fd {
-        if let Some(JsValue::Float(a)) = atime {
-            if let Some(JsValue::Float(m)) = mtime {
+        if let Some(a) = get_js_time_ns(atime) {
+            if let Some(m) = get_js_time_ns(mtime) {
                 let res = unsafe {
                     wasi::fd_filestat_set_times(
                         *f as u32,
-                        *a as u64,
-                        *m as u64,
+                        a,
+                        m,
                         wasi::FSTFLAGS_ATIM | wasi::FSTFLAGS_MTIM,
                     )
                 };
@@ -668,41 +685,55 @@ fn get_js_number(val: Option<&JsValue>) -> Option<i64> {
     match val {
         Some(JsValue::Int(i)) => Some(*i as i64),
         Some(JsValue::Float(f)) => Some(*f as i64),
+        Some(JsValue::BigNum(n)) => n.to_i64(),
         _ => None,
     }
 }
 
-fn fread(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
-    if let Some(JsValue::Int(fd)) = arg.get(0) {
-        if let Some(position) = get_js_number(arg.get(1)) {
-            if let Some(JsValue::Int(length)) = arg.get(2) {
-                let (promise, ok, error) = ctx.new_promise();
-                if let Some(event_loop) = ctx.event_loop() {
-                    event_loop.fd_read(
-                        *fd,
-                        position,
-                        *length as u64,
-                        Box::new(move |ctx, res| match res {
-                            PollResult::Read(data) => {
-                                let buf = ctx.new_array_buffer(&data);
-                                if let JsValue::Function(resolve) = ok {
-                                    resolve.call(&[JsValue::ArrayBuffer(buf)]);
-                                }
-                            }
-                            PollResult::Error(e) => {
-                                if let JsValue::Function(reject) = error {
-                                    reject.call(&[err_to_js_object(ctx, e)]);
-                                }
-                            }
-                            _ => {}
-                        }),
-                    );
-                    return promise;
-                }
-            }
+/// A `Date` or a number of milliseconds since the epoch, in nanoseconds.
+fn get_js_time_ns(val: Option<&JsValue>) -> Option<u64> {
+    let ms = match val {
+        Some(JsValue::Date(date)) => date.time_value(),
+        Some(JsValue::Int(i)) => *i as f64,
+        Some(JsValue::Float(f)) => *f,
+        _ => return None,
+    };
+    if ms.is_finite() && ms >= 0.0 {
+        Some((ms * 1000000.0) as u64)
+    } else {
+        None
+    }
+}
+
+/// Rejection reason of the promise based functions, the same object the
+/// sync functions throw.
+enum FsError {
+    Errno(wasi::Errno),
+    NoEventLoop,
+}
+
+impl IntoJsValue for FsError {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        match self {
+            FsError::Errno(e) => errno_to_js_object(ctx, e),
+            FsError::NoEventLoop => ctx.new_error("no event loop"),
+        }
+    }
+}
+
+fn fread(
+    ctx: &mut Context,
+    (fd, position, length): (i32, i64, u64),
+) -> impl Future<Output = Result<Bytes, FsError>> {
+    let read = ctx
+        .event_loop()
+        .map(|event_loop| event_loop.read(fd, position, length));
+    async move {
+        match read {
+            Some(read) => read.await.map(Bytes).map_err(FsError::Errno),
+            None => Err(FsError::NoEventLoop),
         }
     }
-    return JsValue::UnDefined;
 }
 
 fn fread_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
@@ -734,10 +765,7 @@ fn fread_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
                     }
                 };
                 return match res {
-                    Ok(rlen) => {
-                        let data = ctx.new_array_buffer(&buf[0..rlen]);
-                        JsValue::ArrayBuffer(data)
-                    }
+                    Ok(rlen) => ctx.new_uint8_array(&buf[0..rlen]).into(),
                     Err(e) => {
                         let err = errno_to_js_object(ctx, e);
                         JsValue::Exception(ctx.throw_error(err))
@@ -845,52 +873,32 @@ fn readlink_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsVa
     return JsValue::UnDefined;
 }
 
-fn fwrite(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
-    if let Some(JsValue::Int(fd)) = arg.get(0) {
-        if let Some(position) = get_js_number(arg.get(1)) {
-            if let Some(JsValue::ArrayBuffer(buf)) = arg.get(2) {
-                let (promise, ok, error) = ctx.new_promise();
-                if let Some(event_loop) = ctx.event_loop() {
-                    event_loop.fd_write(
-                        *fd,
-                        position,
-                        buf.to_vec(),
-                        Box::new(move |ctx, res| match res {
-                            PollResult::Write(len) => {
-                                if let JsValue::Function(resolve) = ok {
-                                    resolve.call(&[JsValue::Int(len as i32)]);
-                                }
-                            }
-                            PollResult::Error(e) => {
-                                if let JsValue::Function(reject) = error {
-                                    reject.call(&[err_to_js_object(ctx, e)]);
-                                }
-                            }
-                            _ => {}
-                        }),
-                    );
-                    return promise;
-                }
-            }
+fn fwrite(
+    ctx: &mut Context,
+    (fd, position, Bytes(buf)): (i32, i64, Bytes),
+) -> impl Future<Output = Result<usize, FsError>> {
+    let write = ctx
+        .event_loop()
+        .map(|event_loop| event_loop.write(fd, position, buf));
+    async move {
+        match write {
+            Some(write) => write.await.map_err(FsError::Errno),
+            None => Err(FsError::NoEventLoop),
         }
     }
-    return JsValue::UnDefined;
 }
 
 fn fwrite_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     if let Some(JsValue::Int(fd)) = arg.get(0) {
-        if let Some(JsValue::Int(position)) = arg.get(1) {
-            if let Some(JsValue::Object(obj)) = arg.get(2) {
-                let buf = JsArrayBuffer(obj.js_ref().to_owned());
-                if *position >= 0 {
-                    let res =
-                        unsafe { wasi::fd_seek(*fd as u32, *position as i64, wasi::WHENCE_SET) };
+        if let Some(position) = get_js_number(arg.get(1)) {
+            if let Some(data) = arg.get(2).and_then(JsValue::as_bytes) {
+                if position >= 0 {
+                    let res = unsafe { wasi::fd_seek(*fd as u32, position, wasi::WHENCE_SET) };
                     if let Err(e) = res {
                         let err = errno_to_js_object(ctx, e);
                         return JsValue::Exception(ctx.throw_error(err));
                     }
                 }
-                let data = buf.to_vec();
                 let res = unsafe {
                     wasi::fd_write(
                         *fd as u32,
@@ -913,53 +921,98 @@ fn fwrite_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValu
     return JsValue::UnDefined;
 }
 
-fn freaddir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
-    if let Some(JsValue::Int(fd)) = arg.get(0) {
-        if let Some(JsValue::Int(cookie)) = arg.get(1) {
-            let mut buf = vec![0; 4096];
-            let res = unsafe {
-                wasi::fd_readdir(*fd as u32, buf.as_mut_ptr(), buf.len(), *cookie as u64)
-            };
-            return match res {
-                Ok(len) => {
-                    let s = std::mem::size_of::<wasi::Dirent>();
-                    let mut idx = 0;
-                    let mut data_pack = ctx.new_array();
-                    let mut aidx = 0;
-                    let mut dir_next = 0;
-                    while (idx + s) < len.min(4096) {
-                        let dir = unsafe {
-                            *(&buf[idx..(idx + s)] as *const [u8] as *const wasi::Dirent)
-                        };
-                        idx += s;
-                        if (idx + dir.d_namlen as usize) >= len.min(4096) {
-                            break;
-                        }
-                        let name =
-                            String::from_utf8_lossy(&buf[idx..(idx + dir.d_namlen as usize)])
-                                .to_string();
-                        idx += dir.d_namlen as usize;
-                        let mut dirent = ctx.new_object();
+#[derive(IntoJsValue)]
+struct DirEntry {
+    filetype: i32,
+    name: String,
+}
 
-                        dirent.set("filetype", JsValue::Int(dir.d_type.raw() as i32));
-                        dirent.set("name", ctx.new_string(name.as_str()).into());
-                        data_pack.put(aidx, dirent.into());
-                        dir_next = dir.d_next;
-                        aidx += 1;
-                    }
-                    let mut data = ctx.new_object();
-                    data.set("res", data_pack.into());
-                    data.set("fin", (len < buf.len()).into());
-                    data.set("cookie", JsValue::Int(dir_next as i32));
-                    data.into()
-                }
-                Err(e) => {
-                    let err = errno_to_js_object(ctx, e);
-                    JsValue::Exception(ctx.throw_error(err))
-                }
-            };
+/// A `wasi::Errno` thrown as the object `errno_to_js_object` makes.
+struct Errno(wasi::Errno);
+
+impl IntoJsValue for Errno {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        errno_to_js_object(ctx, self.0)
+    }
+}
+
+/// The entries of a directory, read with `fd_readdir` one buffer at a time
+/// as they are asked for.
+struct ReadDir {
+    fd: Fd,
+    cookie: wasi::Dircookie,
+    buf: Vec<u8>,
+    entries: std::vec::IntoIter<DirEntry>,
+    fin: bool,
+}
+
+impl ReadDir {
+    fn new(fd: Fd) -> Self {
+        ReadDir {
+            fd,
+            cookie: 0,
+            buf: vec![0; 4096],
+            entries: Vec::new().into_iter(),
+            fin: false,
+        }
+    }
+
+    fn fill(&mut self) -> Result<(), wasi::Errno> {
+        let len = unsafe {
+            wasi::fd_readdir(self.fd, self.buf.as_mut_ptr(), self.buf.len(), self.cookie)?
+        };
+        let len = len.min(self.buf.len());
+        let s = std::mem::size_of::<wasi::Dirent>();
+        let mut idx = 0;
+        let mut entries = vec![];
+        // the last entry is cut off when the buffer is full, it is read again
+        // from its cookie next time
+        while idx + s <= len {
+            let dir = unsafe { *(&self.buf[idx..(idx + s)] as *const [u8] as *const wasi::Dirent) };
+            let name_end = idx + s + dir.d_namlen as usize;
+            if name_end > len {
+                break;
+            }
+            let name = String::from_utf8_lossy(&self.buf[(idx + s)..name_end]).to_string();
+            idx = name_end;
+            entries.push(DirEntry {
+                filetype: dir.d_type.raw() as i32,
+                name,
+            });
+            self.cookie = dir.d_next;
+        }
+        // an entry too long for the buffer would be read again forever
+        self.fin = len < self.buf.len() || entries.is_empty();
+        self.entries = entries.into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry, Errno>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.next() {
+                return Some(Ok(entry));
+            }
+            if self.fin {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.fin = true;
+                return Some(Err(Errno(e)));
+            }
         }
     }
+}
+
+/// A lazy iterator over the `{ filetype, name }` entries of the directory
+/// `fd`, which throws from `next()` when reading fails.
+fn freaddir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    if let Some(JsValue::Int(fd)) = arg.get(0) {
+        return ctx.new_iterator(ReadDir::new(*fd as Fd)).into();
+    }
     return JsValue::UnDefined;
 }
 
@@ -987,11 +1040,11 @@ impl ModuleInit for FS {
         let fsync_s = ctx.wrap_function("fsyncSync", fsync_sync);
         let fdatasync_s = ctx.wrap_function("fdatasyncSync", fdatasync_sync);
         let fread_s = ctx.wrap_function("freadSync", fread_sync);
-        let fread_a = ctx.wrap_function("fread", fread);
+        let fread_a = ctx.new_async_function("fread", fread);
         let open_s = ctx.wrap_function("openSync", open_sync);
         let readlink_s = ctx.wrap_function("readlinkSync", readlink_sync);
         let fwrite_s = ctx.wrap_function("fwriteSync", fwrite_sync);
-        let fwrite_a = ctx.wrap_function("fwrite", fwrite);
+        let fwrite_a = ctx.new_async_function("fwrite", fwrite);
         let freaddir_s = ctx.wrap_function("freaddirSync", freaddir_sync);
         m.add_export("statSync", stat_s.into());
         m.add_export("lstatSync", lstat_s.into());
@@ -4,3 +4,5 @@ pub mod fs;
 pub mod os;
 pub mod sys;
 pub mod tty;
+pub mod v8;
+pub mod vm;
@@ -15,6 +15,9 @@ fn require(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
     let path = argv.get(0);
     if let Some(JsValue::String(path)) = path {
         let path = path.to_string();
+        if let Some(event_loop) = ctx.event_loop() {
+            event_loop.record_loaded_file(&path);
+        }
         let code = resolver::require(&path).expect("sys: invalid require path");
         let code = String::from_utf8(code).expect("invalid require content encoding");
         ctx.new_string(code.as_str()).into()
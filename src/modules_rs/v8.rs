@@ -0,0 +1,37 @@
+use crate::quickjs_sys::*;
+
+fn serialize(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    let value = argv.get(0).cloned().unwrap_or(JsValue::UnDefined);
+    match ctx.serialize_value(&value) {
+        Ok(bytes) => ctx.new_array_buffer(&bytes).into(),
+        Err(e) => JsValue::Exception(e),
+    }
+}
+
+fn deserialize(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    // copied, since reading runs JS that could detach the buffer
+    let bytes = match argv.get(0).and_then(JsValue::as_bytes) {
+        Some(bytes) => bytes.to_vec(),
+        None => {
+            let e = ctx.throw_type_error("buffer must be a TypedArray or a DataView");
+            return JsValue::Exception(e);
+        }
+    };
+    ctx.deserialize_value(&bytes)
+        .unwrap_or_else(JsValue::Exception)
+}
+
+struct V8;
+
+impl ModuleInit for V8 {
+    fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
+        let f = ctx.wrap_function("serialize", serialize);
+        m.add_export("serialize\0", f.into());
+        let f = ctx.wrap_function("deserialize", deserialize);
+        m.add_export("deserialize\0", f.into());
+    }
+}
+
+pub fn init_module(ctx: &mut Context) {
+    ctx.register_module("_node:v8\0", V8, &["serialize\0", "deserialize\0"])
+}
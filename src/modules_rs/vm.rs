@@ -0,0 +1,65 @@
+use crate::quickjs_sys::*;
+
+/// A separate context on the caller's runtime, with only the ECMAScript
+/// built-ins. `vm.js` keeps the sandbox object and its global in sync.
+struct VmContext;
+
+impl JsClassDef for VmContext {
+    type RefType = Context;
+
+    const CLASS_NAME: &'static str = "VmContext";
+    const CONSTRUCTOR_ARGC: u8 = 0;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] =
+        &[("global", 0, global), ("runScript", 2, run_script)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut CLASS_ID
+    }
+
+    fn constructor_fn(ctx: &mut Context, _argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        Ok(ContextBuilder::bare().build_sibling(ctx))
+    }
+}
+
+fn global(
+    inner: &mut Context,
+    _this: &mut JsObject,
+    _ctx: &mut Context,
+    _argv: &[JsValue],
+) -> JsValue {
+    inner.get_global().into()
+}
+
+fn run_script(
+    inner: &mut Context,
+    _this: &mut JsObject,
+    ctx: &mut Context,
+    argv: &[JsValue],
+) -> JsValue {
+    let code = match argv.get(0) {
+        Some(JsValue::String(code)) => code.to_string(),
+        _ => return JsValue::Exception(ctx.throw_type_error("code must be a string")),
+    };
+    let filename = match argv.get(1) {
+        Some(JsValue::String(filename)) => filename.to_string(),
+        _ => "evalmachine.<anonymous>".to_string(),
+    };
+    inner.eval_global_str_for(ctx, code, &filename)
+}
+
+struct VM;
+
+impl ModuleInit for VM {
+    fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
+        let class_ctor = register_class::<VmContext>(ctx);
+        m.add_export("VmContext\0", class_ctor);
+    }
+}
+
+pub fn init_module(ctx: &mut Context) {
+    ctx.register_module("_node:vm\0", VM, &["VmContext\0"])
+}
@@ -98,11 +98,9 @@ function toIsoWASI(wasi: any, abi: ABIVariant): IsoWASI {
  */
 export async function runDrop(opts: RunOptions): Promise<Runner> {
 	const variant = opts.variant || getDefaultABIVariant();
-	const file = opts.Module.arguments.find((arg) => !arg.startsWith("-"));
-	const newArgs = opts.Module.arguments.filter((arg) => !arg.startsWith("-"));
 	const sharedOpts = {
 		preopens: { [process.cwd()]: ".", ".": "." },
-		args: ["drop", file, ...newArgs],
+		args: ["drop", ...opts.Module.arguments],
 		env: process.env,
 	};
 	let NodeWASI: NodeWASI;
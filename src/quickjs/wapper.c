@@ -131,6 +131,180 @@ int JS_IsPromise(JSContext *ctx, JSValueConst val) {
     }
 }
 
+int JS_GetBufferKind_real(JSValueConst val) {
+    JSObject *p;
+    if (JS_VALUE_GET_TAG(val) != JS_TAG_OBJECT)
+        return JS_BUFFER_KIND_NONE;
+    p = JS_VALUE_GET_OBJ(val);
+    switch (p->class_id) {
+    case JS_CLASS_ARRAY_BUFFER:
+    case JS_CLASS_SHARED_ARRAY_BUFFER:
+        return JS_BUFFER_KIND_ARRAY_BUFFER;
+    case JS_CLASS_DATAVIEW:
+        return JS_BUFFER_KIND_DATA_VIEW;
+    default:
+        if (p->class_id >= JS_CLASS_UINT8C_ARRAY && p->class_id <= JS_CLASS_FLOAT64_ARRAY)
+            return JS_BUFFER_KIND_UINT8C_ARRAY + (p->class_id - JS_CLASS_UINT8C_ARRAY);
+        return JS_BUFFER_KIND_NONE;
+    }
+}
+
+/* The data of the buffer behind a typed array or DataView, which
+   JS_GetTypedArrayBuffer does not accept, and the byte range `obj` views.
+   Returns NULL for a detached buffer. */
+uint8_t *JS_GetArrayBufferView_real(JSValueConst obj, size_t *pbyte_offset, size_t *pbyte_length) {
+    JSTypedArray *ta = JS_VALUE_GET_OBJ(obj)->u.typed_array;
+    JSArrayBuffer *abuf = ta->obj->u.array_buffer;
+    *pbyte_offset = ta->offset;
+    *pbyte_length = ta->length;
+    if (abuf->detached) {
+        *pbyte_length = 0;
+        return NULL;
+    }
+    return abuf->data;
+}
+
+JSValue JS_GetArrayBufferViewBuffer_real(JSContext *ctx, JSValueConst obj) {
+    JSTypedArray *ta = JS_VALUE_GET_OBJ(obj)->u.typed_array;
+    return JS_DupValue(ctx, JS_MKPTR(JS_TAG_OBJECT, ta->obj));
+}
+
+/* `new Uint8Array(buffer, byte_offset, length)` and friends for the
+   JS_BUFFER_KIND_* of a typed array or DataView. `length` counts elements,
+   bytes for a DataView. */
+JSValue JS_NewArrayBufferView_real(JSContext *ctx, int kind, JSValueConst buffer, size_t byte_offset, size_t length) {
+    JSValue argv[3];
+    JSValue ret;
+    argv[0] = buffer;
+    argv[1] = JS_NewInt64(ctx, byte_offset);
+    argv[2] = JS_NewInt64(ctx, length);
+    if (kind == JS_BUFFER_KIND_DATA_VIEW)
+        ret = js_dataview_constructor(ctx, JS_UNDEFINED, 3, argv);
+    else if (kind >= JS_BUFFER_KIND_UINT8C_ARRAY && kind <= JS_BUFFER_KIND_FLOAT64_ARRAY)
+        ret = js_typed_array_constructor(ctx, JS_UNDEFINED, 3, argv,
+                                         JS_CLASS_UINT8C_ARRAY + (kind - JS_BUFFER_KIND_UINT8C_ARRAY));
+    else
+        ret = JS_ThrowTypeError(ctx, "not a typed array or DataView kind");
+    JS_FreeValue(ctx, argv[1]);
+    JS_FreeValue(ctx, argv[2]);
+    return ret;
+}
+
+int JS_GetObjectKind_real(JSValueConst val) {
+    if (JS_VALUE_GET_TAG(val) != JS_TAG_OBJECT)
+        return JS_OBJECT_KIND_OTHER;
+    switch (JS_VALUE_GET_OBJ(val)->class_id) {
+    case JS_CLASS_DATE:
+        return JS_OBJECT_KIND_DATE;
+    case JS_CLASS_REGEXP:
+        return JS_OBJECT_KIND_REGEXP;
+    case JS_CLASS_MAP:
+        return JS_OBJECT_KIND_MAP;
+    case JS_CLASS_SET:
+        return JS_OBJECT_KIND_SET;
+    default:
+        return JS_OBJECT_KIND_OTHER;
+    }
+}
+
+/* The time value of a Date, NaN for an invalid date. */
+double JS_GetDateValue_real(JSValueConst obj) {
+    JSValueConst v = JS_VALUE_GET_OBJ(obj)->u.object_data;
+    if (JS_VALUE_GET_TAG(v) == JS_TAG_INT)
+        return JS_VALUE_GET_INT(v);
+    if (JS_TAG_IS_FLOAT64(JS_VALUE_GET_TAG(v)))
+        return JS_VALUE_GET_FLOAT64(v);
+    return NAN;
+}
+
+/* `new Date(epoch_ms)` without looking up the global constructor. */
+JSValue JS_NewDate_real(JSContext *ctx, double epoch_ms) {
+    JSValue obj = js_create_from_ctor(ctx, JS_UNDEFINED, JS_CLASS_DATE);
+    if (!JS_IsException(obj))
+        JS_SetObjectData(ctx, obj, JS_NewFloat64(ctx, time_clip(epoch_ms)));
+    return obj;
+}
+
+/* %IteratorPrototype%, whose [Symbol.iterator]() returns `this`. */
+JSValue JS_GetIteratorPrototype_real(JSContext *ctx) {
+    return JS_DupValue(ctx, ctx->iterator_proto);
+}
+
+/* Ordinary objects are cloned by their own enumerable properties, errors and
+   `new Number(1)` and friends by what they wrap. Everything else, proxies,
+   weak collections and objects of native classes, can't be cloned. */
+int JS_GetCloneKind_real(JSValueConst val) {
+    if (JS_VALUE_GET_TAG(val) != JS_TAG_OBJECT)
+        return JS_CLONE_KIND_UNCLONEABLE;
+    switch (JS_VALUE_GET_OBJ(val)->class_id) {
+    case JS_CLASS_OBJECT:
+    case JS_CLASS_ARGUMENTS:
+    case JS_CLASS_MAPPED_ARGUMENTS:
+        return JS_CLONE_KIND_PLAIN;
+    case JS_CLASS_ERROR:
+        return JS_CLONE_KIND_ERROR;
+    case JS_CLASS_NUMBER:
+    case JS_CLASS_STRING:
+    case JS_CLASS_BOOLEAN:
+#ifdef CONFIG_BIGNUM
+    case JS_CLASS_BIG_INT:
+#endif
+        return JS_CLONE_KIND_PRIMITIVE_WRAPPER;
+    default:
+        return JS_CLONE_KIND_UNCLONEABLE;
+    }
+}
+
+/* The primitive of an object JS_GetCloneKind_real reports as a wrapper. */
+JSValue JS_GetPrimitiveWrapperValue_real(JSContext *ctx, JSValueConst obj) {
+    return JS_DupValue(ctx, JS_VALUE_GET_OBJ(obj)->u.object_data);
+}
+
+/* `Object(val)`, the wrapper object of a primitive. */
+JSValue JS_NewPrimitiveWrapper_real(JSContext *ctx, JSValueConst val) {
+    return JS_ToObject(ctx, val);
+}
+
+/* How many references `v` has, 0 for values that aren't counted. */
+int JS_GetRefCount_real(JSValueConst v) {
+    if (!JS_VALUE_HAS_REF_COUNT(v))
+        return 0;
+    return ((JSRefCountHeader *)JS_VALUE_GET_PTR(v))->ref_count;
+}
+
+/* An InternalError that try/catch can't stop, like the one of an interrupt. */
+JSValue JS_ThrowUncatchableError_real(JSContext *ctx, const char *msg) {
+    JS_ThrowInternalError(ctx, "%s", msg);
+    JS_SetUncatchableError(ctx, ctx->rt->current_exception, TRUE);
+    return JS_EXCEPTION;
+}
+
+/* The well-known symbol `offset` atoms after Symbol.toPrimitive, as kept by
+   the runtime, so a replaced global `Symbol` doesn't matter. */
+JSValue JS_GetWellKnownSymbol_real(JSContext *ctx, int offset) {
+    if (offset < 0 || offset > JS_ATOM_Symbol_asyncIterator - JS_ATOM_Symbol_toPrimitive)
+        return JS_UNDEFINED;
+    return JS_AtomToValue(ctx, JS_ATOM_Symbol_toPrimitive + offset);
+}
+
+/* A new ArrayBuffer that takes over the memory of `obj`, which is left
+   detached, so a transferred buffer is moved instead of copied. */
+JSValue JS_TransferArrayBuffer_real(JSContext *ctx, JSValueConst obj) {
+    JSArrayBuffer *abuf = JS_GetOpaque(obj, JS_CLASS_ARRAY_BUFFER);
+    JSValue moved;
+
+    if (!abuf || abuf->detached)
+        return JS_ThrowTypeError(ctx, "ArrayBuffer is detached");
+    moved = JS_NewArrayBuffer(ctx, abuf->data, abuf->byte_length,
+                              abuf->free_func, abuf->opaque, FALSE);
+    if (JS_IsException(moved))
+        return moved;
+    /* owned by `moved` now, detaching must not free it */
+    abuf->free_func = NULL;
+    JS_DetachArrayBuffer(ctx, obj);
+    return moved;
+}
+
 int js_eval_buf(JSContext *ctx, const void *buf, int buf_len, const char *filename, int eval_flags)
 {
     JSValue val;
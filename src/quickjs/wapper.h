@@ -71,6 +71,65 @@ JS_BOOL JS_IsObject_real(JSValueConst v);
 
 int JS_IsPromise(JSContext *ctx, JSValueConst val);
 
+/* what JS_GetBufferKind_real reports, typed arrays in JS_CLASS_*_ARRAY order */
+#define JS_BUFFER_KIND_NONE 0
+#define JS_BUFFER_KIND_ARRAY_BUFFER 1
+#define JS_BUFFER_KIND_UINT8C_ARRAY 2
+#define JS_BUFFER_KIND_INT8_ARRAY 3
+#define JS_BUFFER_KIND_UINT8_ARRAY 4
+#define JS_BUFFER_KIND_INT16_ARRAY 5
+#define JS_BUFFER_KIND_UINT16_ARRAY 6
+#define JS_BUFFER_KIND_INT32_ARRAY 7
+#define JS_BUFFER_KIND_UINT32_ARRAY 8
+#define JS_BUFFER_KIND_BIG_INT64_ARRAY 9
+#define JS_BUFFER_KIND_BIG_UINT64_ARRAY 10
+#define JS_BUFFER_KIND_FLOAT32_ARRAY 11
+#define JS_BUFFER_KIND_FLOAT64_ARRAY 12
+#define JS_BUFFER_KIND_DATA_VIEW 13
+
+int JS_GetBufferKind_real(JSValueConst val);
+
+uint8_t *JS_GetArrayBufferView_real(JSValueConst obj, size_t *pbyte_offset, size_t *pbyte_length);
+
+JSValue JS_GetArrayBufferViewBuffer_real(JSContext *ctx, JSValueConst obj);
+
+JSValue JS_NewArrayBufferView_real(JSContext *ctx, int kind, JSValueConst buffer, size_t byte_offset, size_t length);
+
+/* what JS_GetObjectKind_real reports for the builtins with their own JsValue variant */
+#define JS_OBJECT_KIND_OTHER 0
+#define JS_OBJECT_KIND_DATE 1
+#define JS_OBJECT_KIND_REGEXP 2
+#define JS_OBJECT_KIND_MAP 3
+#define JS_OBJECT_KIND_SET 4
+
+int JS_GetObjectKind_real(JSValueConst val);
+
+double JS_GetDateValue_real(JSValueConst obj);
+
+JSValue JS_NewDate_real(JSContext *ctx, double epoch_ms);
+
+JSValue JS_GetIteratorPrototype_real(JSContext *ctx);
+
+/* how structured clone treats an object without a JsValue variant of its own */
+#define JS_CLONE_KIND_PLAIN 0
+#define JS_CLONE_KIND_ERROR 1
+#define JS_CLONE_KIND_PRIMITIVE_WRAPPER 2
+#define JS_CLONE_KIND_UNCLONEABLE 3
+
+int JS_GetCloneKind_real(JSValueConst val);
+
+JSValue JS_GetPrimitiveWrapperValue_real(JSContext *ctx, JSValueConst obj);
+
+JSValue JS_NewPrimitiveWrapper_real(JSContext *ctx, JSValueConst val);
+
+int JS_GetRefCount_real(JSValueConst v);
+
+JSValue JS_ThrowUncatchableError_real(JSContext *ctx, const char *msg);
+
+JSValue JS_GetWellKnownSymbol_real(JSContext *ctx, int offset);
+
+JSValue JS_TransferArrayBuffer_real(JSContext *ctx, JSValueConst obj);
+
 JSValue JS_GetPromiseResult_real(JSContext *ctx, JSValueConst this_val);
 
 int JS_ToUint32_real(JSContext *ctx, uint32_t *pres, JSValueConst val);
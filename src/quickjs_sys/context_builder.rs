@@ -0,0 +1,219 @@
+use super::qjs::*;
+use super::{js_init_dirname, Context, Runtime};
+
+// the context opaque of a context built with `fs(false)`
+static NO_FS: u8 = 0;
+
+/// Whether `ctx` may load modules from disk, see `ContextBuilder::fs`.
+pub(crate) unsafe fn has_fs(ctx: *mut JSContext) -> bool {
+    JS_GetContextOpaque(ctx) as *const u8 != &NO_FS as *const u8
+}
+
+/// Chooses what a new `Context` gets installed. `Runtime::new_context` is the
+/// same as `ContextBuilder::new().build(rt)`, which enables everything.
+///
+/// ```ignore
+/// let mut ctx = ContextBuilder::new()
+///     .fs(false)
+///     .with_module(|ctx| ctx.register_module("_app\0", App, &["version\0"]))
+///     .build(&mut rt);
+/// ```
+pub struct ContextBuilder {
+    std: bool,
+    globals: bool,
+    dirname: bool,
+    encoding: bool,
+    os: bool,
+    fs: bool,
+    tty: bool,
+    sys: bool,
+    v8: bool,
+    vm: bool,
+    modules: Vec<Box<dyn FnOnce(&mut Context)>>,
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        ContextBuilder {
+            std: true,
+            globals: true,
+            dirname: true,
+            encoding: true,
+            os: true,
+            fs: true,
+            tty: true,
+            sys: true,
+            v8: true,
+            vm: true,
+            modules: vec![],
+        }
+    }
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only the ECMAScript built-ins, everything else disabled.
+    pub fn bare() -> Self {
+        ContextBuilder {
+            std: false,
+            globals: false,
+            dirname: false,
+            encoding: false,
+            os: false,
+            fs: false,
+            tty: false,
+            sys: false,
+            v8: false,
+            vm: false,
+            modules: vec![],
+        }
+    }
+
+    /// The `std` and `qjs:os` modules and `console` from quickjs-libc. The
+    /// modules need `fs` as well.
+    pub fn std(mut self, enable: bool) -> Self {
+        self.std = enable;
+        self
+    }
+
+    /// `setTimeout`, `clearTimeout`, `setImmediate`, `nextTick`, `exit`,
+    /// `env`, `structuredClone` and `_hrtimeBigint` on the global object.
+    /// `process` falls back to less precise versions of what it uses of
+    /// these when they are left out.
+    pub fn globals(mut self, enable: bool) -> Self {
+        self.globals = enable;
+        self
+    }
+
+    /// The `__dirname` global.
+    pub fn dirname(mut self, enable: bool) -> Self {
+        self.dirname = enable;
+        self
+    }
+
+    /// The `_encoding` module behind `TextEncoder`/`TextDecoder`.
+    pub fn encoding(mut self, enable: bool) -> Self {
+        self.encoding = enable;
+        self
+    }
+
+    /// The `_node:os` module behind `os`.
+    pub fn os(mut self, enable: bool) -> Self {
+        self.os = enable;
+        self
+    }
+
+    /// The file system: the `_node:fs` module behind `fs`, the `std` and
+    /// `qjs:os` modules and `_drop:sys`, whatever `std` and `sys` say, and
+    /// loading modules from disk. Without it only the embedded modules and
+    /// those registered natively can be imported, and `console` is all that
+    /// is left of `std`.
+    pub fn fs(mut self, enable: bool) -> Self {
+        self.fs = enable;
+        self
+    }
+
+    /// The `_node:tty` module behind `tty`.
+    pub fn tty(mut self, enable: bool) -> Self {
+        self.tty = enable;
+        self
+    }
+
+    /// The `_drop:sys` module, which `require` needs to load CommonJS modules.
+    /// Needs `fs` as well.
+    pub fn sys(mut self, enable: bool) -> Self {
+        self.sys = enable;
+        self
+    }
+
+    /// The `_node:v8` module behind `v8`.
+    pub fn v8(mut self, enable: bool) -> Self {
+        self.v8 = enable;
+        self
+    }
+
+    /// The `_node:vm` module behind `vm`.
+    pub fn vm(mut self, enable: bool) -> Self {
+        self.vm = enable;
+        self
+    }
+
+    /// Runs `f` on the context after the built-ins are installed, to register
+    /// native modules with `Context::register_module` and friends or to add
+    /// globals. Called in the order they were added.
+    pub fn with_module<F: FnOnce(&mut Context) + 'static>(mut self, f: F) -> Self {
+        self.modules.push(Box::new(f));
+        self
+    }
+
+    pub fn build(self, rt: &mut Runtime) -> Context {
+        unsafe { self.build_with_rt(rt.0) }
+    }
+
+    /// Builds a context on the runtime `ctx` belongs to. Values can be passed
+    /// freely between contexts of the same runtime.
+    pub fn build_sibling(self, ctx: &mut Context) -> Context {
+        unsafe { self.build_with_rt(ctx.rt()) }
+    }
+
+    pub(crate) unsafe fn build_with_rt(self, rt: *mut JSRuntime) -> Context {
+        let ctx = JS_NewContext(rt);
+        JS_AddIntrinsicBigFloat(ctx);
+        JS_AddIntrinsicBigDecimal(ctx);
+        JS_AddIntrinsicOperators(ctx);
+        JS_AddIntrinsicEval(ctx);
+        JS_AddIntrinsicDate(ctx);
+        JS_EnableBignumExt(ctx, 1);
+        if self.std {
+            js_std_add_console(ctx);
+            // both open, read and write files
+            if self.fs {
+                js_init_module_std(ctx, "std\0".as_ptr() as *const i8);
+                js_init_module_os(ctx, "qjs:os\0".as_ptr() as *const i8);
+            }
+        }
+        if !self.fs {
+            JS_SetContextOpaque(ctx, &NO_FS as *const u8 as *mut _);
+        }
+        let mut ctx = Context { ctx };
+
+        if self.dirname {
+            js_init_dirname(&mut ctx);
+        }
+
+        if self.globals {
+            crate::modules_rs::core::init_global_function(&mut ctx);
+            crate::modules_rs::core::init_ext_function(&mut ctx);
+        }
+        if self.encoding {
+            crate::modules_rs::encoding::init_encoding_module(&mut ctx);
+        }
+        if self.os {
+            crate::modules_rs::os::init_module(&mut ctx);
+        }
+        if self.fs {
+            crate::modules_rs::fs::init_module(&mut ctx);
+        }
+        if self.tty {
+            crate::modules_rs::tty::init_module(&mut ctx);
+        }
+        if self.sys && self.fs {
+            crate::modules_rs::sys::init_module(&mut ctx);
+        }
+        if self.v8 {
+            crate::modules_rs::v8::init_module(&mut ctx);
+        }
+        if self.vm {
+            crate::modules_rs::vm::init_module(&mut ctx);
+        }
+
+        for f in self.modules {
+            f(&mut ctx);
+        }
+
+        ctx
+    }
+}
@@ -0,0 +1,120 @@
+use super::qjs::*;
+use super::{AsObject, Context, FromJsArgs, IntoJsValue, JsFunction, JsObject, JsPromise, JsValue};
+use crate::event_loop::{check_callback_result, pending, Pending};
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::rc::Rc;
+
+// Rejections want the error value itself. An `IntoJsValue` that throws, like
+// `ConvertError` inside a `Result`, leaves it as the pending exception.
+fn into_reason<E: IntoJsValue>(ctx: &mut Context, e: E) -> JsValue {
+    match e.into_js_value(ctx) {
+        JsValue::Exception(_) => unsafe {
+            JsValue::from_qjs_value(ctx.ctx, JS_GetException(ctx.ctx))
+        },
+        reason => reason,
+    }
+}
+
+fn settle(ctx: &mut Context, callback: JsValue, value: JsValue) {
+    if let JsValue::Function(callback) = callback {
+        let res = callback.call(&[value]);
+        check_callback_result(ctx, res);
+    }
+}
+
+impl Context {
+    /// Makes a JS function that returns a promise for the future `f` returns.
+    /// The future runs on the event loop, see `EventLoop::spawn_local`, and
+    /// the promise is resolved with `Ok` or rejected with `Err`. Arguments of
+    /// the wrong type reject it with a `TypeError`.
+    ///
+    /// ```ignore
+    /// let sleep = ctx.new_async_function("sleep", |ctx, (ms,): (u64,)| {
+    ///     let done = ctx.event_loop().unwrap().sleep(Duration::from_millis(ms));
+    ///     async move {
+    ///         done.await;
+    ///         Ok::<_, JsValue>(())
+    ///     }
+    /// });
+    /// ```
+    pub fn new_async_function<A, F, Fut, T, E>(&mut self, name: &str, f: F) -> JsFunction
+    where
+        A: FromJsArgs,
+        F: Fn(&mut Context, A) -> Fut + 'static,
+        Fut: Future<Output = Result<T, E>> + 'static,
+        T: IntoJsValue + 'static,
+        E: IntoJsValue + 'static,
+    {
+        self.new_closure_with_length(
+            name,
+            A::LENGTH,
+            Box::new(move |ctx, _this, argv| {
+                let (promise, resolve, reject) = ctx.new_promise();
+                let args = match A::from_js_args(ctx, argv) {
+                    Ok(args) => args,
+                    Err(e) => {
+                        let reason = e.into_js_value(ctx);
+                        settle(ctx, reject, reason);
+                        return promise;
+                    }
+                };
+                let future = f(ctx, args);
+                match ctx.event_loop() {
+                    Some(event_loop) => {
+                        event_loop.spawn_local(async move {
+                            let result = future.await;
+                            Box::new(move |ctx: &mut Context| match result {
+                                Ok(value) => {
+                                    let value = value.into_js_value(ctx);
+                                    settle(ctx, resolve, value);
+                                }
+                                Err(e) => {
+                                    let reason = into_reason(ctx, e);
+                                    settle(ctx, reject, reason);
+                                }
+                            }) as crate::Completion
+                        });
+                    }
+                    None => {
+                        let reason = ctx.new_error("no event loop to run the async function");
+                        settle(ctx, reject, reason);
+                    }
+                }
+                promise
+            }),
+        )
+    }
+}
+
+impl JsPromise {
+    /// A future for the outcome of the promise, `Err` with the reason when it
+    /// is rejected. It settles while the event loop runs the promise jobs, so
+    /// it is meant to be awaited in a future passed to
+    /// `EventLoop::spawn_local`.
+    pub fn into_future(self) -> Pending<Result<JsValue, JsValue>> {
+        let (completer, pending) = pending();
+        // only one of the two reactions runs
+        let completer = Rc::new(RefCell::new(Some(completer)));
+        let mut ctx = std::mem::ManuallyDrop::new(Context { ctx: self.0.ctx });
+
+        let fulfilled = completer.clone();
+        let on_fulfilled = ctx.new_closure("", move |_ctx, _this, argv| {
+            if let Some(completer) = fulfilled.borrow_mut().take() {
+                completer.complete(Ok(argv.get(0).cloned().unwrap_or(JsValue::UnDefined)));
+            }
+            JsValue::UnDefined
+        });
+        let on_rejected = ctx.new_closure("", move |_ctx, _this, argv| {
+            if let Some(completer) = completer.borrow_mut().take() {
+                completer.complete(Err(argv.get(0).cloned().unwrap_or(JsValue::UnDefined)));
+            }
+            JsValue::UnDefined
+        });
+
+        let mut promise = JsObject(self.0);
+        promise.invoke("then", &[on_fulfilled.into(), on_rejected.into()]);
+        pending
+    }
+}
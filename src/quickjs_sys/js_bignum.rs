@@ -0,0 +1,175 @@
+use super::qjs::*;
+use super::{AsObject, Context, ConvertError, FromJsValue, IntoJsValue, JsBigNum, JsRef, JsValue};
+
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BigNumKind {
+    BigInt,
+    BigFloat,
+    BigDecimal,
+}
+
+impl BigNumKind {
+    /// The global constructor, which also parses strings.
+    fn constructor(self) -> &'static str {
+        match self {
+            BigNumKind::BigInt => "BigInt",
+            BigNumKind::BigFloat => "BigFloat",
+            BigNumKind::BigDecimal => "BigDecimal",
+        }
+    }
+}
+
+impl JsBigNum {
+    pub fn kind(&self) -> BigNumKind {
+        match unsafe { JS_VALUE_GET_NORM_TAG_real(self.0.v) } {
+            JS_TAG_BIG_FLOAT => BigNumKind::BigFloat,
+            JS_TAG_BIG_DECIMAL => BigNumKind::BigDecimal,
+            _ => BigNumKind::BigInt,
+        }
+    }
+
+    /// The digits `toString()` gives, in full precision.
+    pub fn to_decimal_string(&self) -> String {
+        unsafe {
+            let v = JS_ToString(self.0.ctx, self.0.v);
+            match JsValue::from_qjs_value(self.0.ctx, v) {
+                JsValue::String(s) => s.to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    /// The value if it is an integer that fits, unlike `to_int64` which wraps
+    /// around.
+    pub fn to_i64(&self) -> Option<i64> {
+        self.to_i128().and_then(|n| i64::try_from(n).ok())
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        self.to_u128().and_then(|n| u64::try_from(n).ok())
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        self.to_decimal_string().parse().ok()
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        self.to_decimal_string().parse().ok()
+    }
+
+    /// The nearest `f64`, losing precision past 2^53.
+    pub fn to_f64(&self) -> f64 {
+        unsafe {
+            let mut n = 0_f64;
+            JS_ToFloat64(self.0.ctx, &mut n, self.0.v);
+            n
+        }
+    }
+}
+
+macro_rules! impl_try_from_bignum {
+    ($($t:ty => $to:ident),*) => {
+        $(
+            impl TryFrom<&JsBigNum> for $t {
+                type Error = ConvertError;
+
+                fn try_from(n: &JsBigNum) -> Result<Self, Self::Error> {
+                    n.$to().ok_or_else(|| ConvertError {
+                        expected: stringify!($t),
+                        found: "bigint",
+                        path: String::new(),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_bignum!(i64 => to_i64, u64 => to_u64, i128 => to_i128, u128 => to_u128);
+
+impl Context {
+    pub fn new_bigint_i64(&mut self, n: i64) -> JsBigNum {
+        unsafe {
+            let v = JS_NewBigInt64(self.ctx, n);
+            JsBigNum(JsRef { ctx: self.ctx, v })
+        }
+    }
+
+    pub fn new_bigint_u64(&mut self, n: u64) -> JsBigNum {
+        unsafe {
+            let v = JS_NewBigUint64(self.ctx, n);
+            JsBigNum(JsRef { ctx: self.ctx, v })
+        }
+    }
+
+    pub fn new_bigint_i128(&mut self, n: i128) -> JsBigNum {
+        match i64::try_from(n) {
+            Ok(n) => self.new_bigint_i64(n),
+            Err(_) => self.parse_bigint(&n.to_string()),
+        }
+    }
+
+    pub fn new_bigint_u128(&mut self, n: u128) -> JsBigNum {
+        match u64::try_from(n) {
+            Ok(n) => self.new_bigint_u64(n),
+            Err(_) => self.parse_bigint(&n.to_string()),
+        }
+    }
+
+    /// Parses `digits` like `BigInt("123")`, `BigFloat("1.5")` or
+    /// `BigDecimal("0.1")` do, without losing precision. Throws a
+    /// `SyntaxError` for a malformed string.
+    pub fn new_bignum_from_str(&mut self, kind: BigNumKind, digits: &str) -> JsValue {
+        let digits = self.new_string(digits);
+        match self.get_global().get(kind.constructor()) {
+            JsValue::Function(f) => f.call(&[digits.into()]),
+            _ => JsValue::Exception(
+                self.throw_internal_type_error(&format!("{} is not available", kind.constructor())),
+            ),
+        }
+    }
+
+    // for integers past 64 bits, which the C API cannot make directly
+    fn parse_bigint(&mut self, digits: &str) -> JsBigNum {
+        match self.new_bignum_from_str(BigNumKind::BigInt, digits) {
+            JsValue::BigNum(n) => n,
+            v => JsBigNum(JsRef {
+                ctx: self.ctx,
+                v: v.into_qjs_value(),
+            }),
+        }
+    }
+}
+
+/// An integer that crosses into JS as a `BigInt`, so it keeps its precision
+/// past 2^53. Numbers that are integers are accepted from JS too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BigInt(pub i128);
+
+impl From<i128> for BigInt {
+    fn from(n: i128) -> Self {
+        BigInt(n)
+    }
+}
+
+impl From<BigInt> for i128 {
+    fn from(n: BigInt) -> Self {
+        n.0
+    }
+}
+
+impl FromJsValue for BigInt {
+    fn from_js_value(ctx: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        i128::from_js_value(ctx, value)
+            .map(BigInt)
+            .map_err(|_| ConvertError::new("bigint", value))
+    }
+}
+
+impl IntoJsValue for BigInt {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_bigint_i128(self.0).into()
+    }
+}
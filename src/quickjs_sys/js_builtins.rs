@@ -0,0 +1,300 @@
+use super::qjs::*;
+use super::{
+    AsObject, Context, ConvertError, FromJsValue, IntoJsValue, JsException, JsRef, JsValue,
+};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(super) fn object_kind(v: JSValue) -> u32 {
+    unsafe { JS_GetObjectKind_real(v) as u32 }
+}
+
+// `method` called on `this` without needing `&mut` like `AsObject::invoke`
+fn call_method(this: &JsRef, method: &str, argv: &[JsValue]) -> JsValue {
+    let mut this = std::mem::ManuallyDrop::new(super::JsObject(JsRef {
+        ctx: this.ctx,
+        v: this.v,
+    }));
+    this.invoke(method, argv)
+}
+
+// the entries of a `Map` or the values of a `Set`, through `Array.from`
+fn to_array(ctx: *mut JSContext, v: &JsRef) -> Result<Vec<JsValue>, JsException> {
+    let mut ctx = std::mem::ManuallyDrop::new(Context { ctx });
+    let this = JsValue::Object(super::JsObject(v.clone()));
+    match ctx.get_global().get("Array") {
+        JsValue::Function(mut array) => match array.invoke("from", &[this]) {
+            JsValue::Array(items) => items.to_vec(),
+            JsValue::Exception(e) => Err(e),
+            _ => Ok(vec![]),
+        },
+        _ => Ok(vec![]),
+    }
+}
+
+/// A `Date`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsDate(pub(super) JsRef);
+
+impl JsDate {
+    /// Milliseconds since the epoch, NaN for an invalid date.
+    pub fn time_value(&self) -> f64 {
+        unsafe { JS_GetDateValue_real(self.0.v) }
+    }
+
+    /// `None` for an invalid date.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let ms = self.time_value();
+        if !ms.is_finite() {
+            return None;
+        }
+        let offset = Duration::from_secs_f64(ms.abs() / 1000.0);
+        if ms < 0.0 {
+            UNIX_EPOCH.checked_sub(offset)
+        } else {
+            UNIX_EPOCH.checked_add(offset)
+        }
+    }
+
+    /// `toISOString()`, `None` for an invalid date.
+    pub fn to_iso_string(&self) -> Option<String> {
+        if !self.time_value().is_finite() {
+            return None;
+        }
+        match call_method(&self.0, "toISOString", &[]) {
+            JsValue::String(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl AsObject for JsDate {
+    fn js_ref(&self) -> &JsRef {
+        &self.0
+    }
+}
+
+/// A `RegExp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsRegExp(pub(super) JsRef);
+
+impl JsRegExp {
+    /// The pattern, as in `/source/flags`.
+    pub fn source(&self) -> String {
+        match self.get("source") {
+            JsValue::String(s) => s.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    pub fn flags(&self) -> String {
+        match self.get("flags") {
+            JsValue::String(s) => s.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// `regexp.test(s)`.
+    pub fn test(&self, s: &str) -> JsValue {
+        let mut ctx = std::mem::ManuallyDrop::new(Context { ctx: self.0.ctx });
+        let s = ctx.new_string(s);
+        call_method(&self.0, "test", &[s.into()])
+    }
+}
+
+impl AsObject for JsRegExp {
+    fn js_ref(&self) -> &JsRef {
+        &self.0
+    }
+}
+
+/// A `Map`. Keys are compared like JS does, so objects are found by
+/// identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsMap(pub(super) JsRef);
+
+impl JsMap {
+    pub fn len(&self) -> usize {
+        match self.get("size") {
+            JsValue::Int(n) => n as usize,
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `map.get(key)`, `undefined` when the key is missing.
+    pub fn lookup(&self, key: &JsValue) -> JsValue {
+        call_method(&self.0, "get", &[key.clone()])
+    }
+
+    pub fn contains_key(&self, key: &JsValue) -> bool {
+        call_method(&self.0, "has", &[key.clone()]) == JsValue::Bool(true)
+    }
+
+    pub fn insert(&mut self, key: JsValue, value: JsValue) -> JsValue {
+        self.invoke("set", &[key, value])
+    }
+
+    pub fn remove(&mut self, key: &JsValue) -> bool {
+        self.invoke("delete", &[key.clone()]) == JsValue::Bool(true)
+    }
+
+    /// The `[key, value]` pairs in insertion order.
+    pub fn entries(&self) -> Result<Vec<(JsValue, JsValue)>, JsException> {
+        Ok(to_array(self.0.ctx, &self.0)?
+            .into_iter()
+            .map(|entry| match entry {
+                JsValue::Array(entry) => (entry.take(0), entry.take(1)),
+                _ => (JsValue::UnDefined, JsValue::UnDefined),
+            })
+            .collect())
+    }
+}
+
+impl AsObject for JsMap {
+    fn js_ref(&self) -> &JsRef {
+        &self.0
+    }
+}
+
+/// A `Set`. Values are compared like JS does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsSet(pub(super) JsRef);
+
+impl JsSet {
+    pub fn len(&self) -> usize {
+        match self.get("size") {
+            JsValue::Int(n) => n as usize,
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, value: &JsValue) -> bool {
+        call_method(&self.0, "has", &[value.clone()]) == JsValue::Bool(true)
+    }
+
+    pub fn insert(&mut self, value: JsValue) -> JsValue {
+        self.invoke("add", &[value])
+    }
+
+    pub fn remove(&mut self, value: &JsValue) -> bool {
+        self.invoke("delete", &[value.clone()]) == JsValue::Bool(true)
+    }
+
+    /// The values in insertion order.
+    pub fn values(&self) -> Result<Vec<JsValue>, JsException> {
+        to_array(self.0.ctx, &self.0)
+    }
+}
+
+impl AsObject for JsSet {
+    fn js_ref(&self) -> &JsRef {
+        &self.0
+    }
+}
+
+impl Context {
+    /// `new Date(ms)`, where `ms` counts milliseconds since the epoch.
+    pub fn new_date(&mut self, ms: f64) -> JsDate {
+        unsafe {
+            let v = JS_NewDate_real(self.ctx, ms);
+            JsDate(JsRef { ctx: self.ctx, v })
+        }
+    }
+
+    pub fn new_date_from_system_time(&mut self, time: SystemTime) -> JsDate {
+        let ms = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs_f64() * 1000.0,
+            Err(e) => -e.duration().as_secs_f64() * 1000.0,
+        };
+        self.new_date(ms)
+    }
+
+    /// `new RegExp(source, flags)`. Throws a `SyntaxError` for an invalid
+    /// pattern or flags.
+    pub fn new_regexp(&mut self, source: &str, flags: &str) -> JsValue {
+        let source = self.new_string(source);
+        let flags = self.new_string(flags);
+        self.construct_global("RegExp", &[source.into(), flags.into()])
+    }
+
+    pub fn new_map(&mut self) -> JsMap {
+        self.new_map_from(Vec::<(JsValue, JsValue)>::new())
+    }
+
+    /// A `Map` of `entries`, e.g. from a `HashMap` or a `BTreeMap`.
+    pub fn new_map_from<K, V, I>(&mut self, entries: I) -> JsMap
+    where
+        K: IntoJsValue,
+        V: IntoJsValue,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = match self.construct_global("Map", &[]) {
+            JsValue::Map(map) => map,
+            v => JsMap(JsRef {
+                ctx: self.ctx,
+                v: v.into_qjs_value(),
+            }),
+        };
+        for (key, value) in entries {
+            let key = key.into_js_value(self);
+            let value = value.into_js_value(self);
+            map.insert(key, value);
+        }
+        map
+    }
+
+    pub fn new_set(&mut self) -> JsSet {
+        self.new_set_from(Vec::<JsValue>::new())
+    }
+
+    /// A `Set` of `values`, e.g. from a `HashSet` or a `BTreeSet`.
+    pub fn new_set_from<T, I>(&mut self, values: I) -> JsSet
+    where
+        T: IntoJsValue,
+        I: IntoIterator<Item = T>,
+    {
+        let mut set = match self.construct_global("Set", &[]) {
+            JsValue::Set(set) => set,
+            v => JsSet(JsRef {
+                ctx: self.ctx,
+                v: v.into_qjs_value(),
+            }),
+        };
+        for value in values {
+            let value = value.into_js_value(self);
+            set.insert(value);
+        }
+        set
+    }
+
+    // `new globalThis[name](...args)`
+    pub(super) fn construct_global(&mut self, name: &str, args: &[JsValue]) -> JsValue {
+        let constructor = self.get_global().get(name);
+        self.call_class_constructor(constructor, args)
+    }
+}
+
+impl FromJsValue for SystemTime {
+    fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        match value {
+            JsValue::Date(date) => date
+                .to_system_time()
+                .ok_or_else(|| ConvertError::new("valid Date", value)),
+            _ => Err(ConvertError::new("Date", value)),
+        }
+    }
+}
+
+impl IntoJsValue for SystemTime {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_date_from_system_time(self).into()
+    }
+}
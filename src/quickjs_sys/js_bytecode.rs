@@ -0,0 +1,177 @@
+use super::qjs::*;
+use super::{make_c_string, resolver};
+use crate::{Context, JsError, JsValue};
+use lazy_static::lazy_static;
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
+use std::sync::Mutex;
+
+const MAGIC: &[u8] = b"qjsbc\0";
+
+lazy_static! {
+    /// Bytecode of every module `module_loader` compiles while
+    /// `Context::compile_module_graph` is running, in load order.
+    static ref RECORDED_MODULES: Mutex<Option<Vec<(String, Vec<u8>)>>> = Mutex::new(None);
+}
+
+pub fn is_bytecode_file(module_name: &str) -> bool {
+    module_name.ends_with(".qjsbc")
+}
+
+unsafe fn write_object(ctx: *mut JSContext, v: JSValue) -> Option<Vec<u8>> {
+    let mut len = 0;
+    let ptr = JS_WriteObject(ctx, &mut len, v, JS_WRITE_OBJ_BYTECODE as i32);
+    if ptr.is_null() {
+        return None;
+    }
+    let buf = std::slice::from_raw_parts(ptr, len).to_vec();
+    js_free(ctx, ptr.cast());
+    Some(buf)
+}
+
+/// Called by `module_loader` for every module it compiles from source.
+/// Embedded modules ship with the runtime and are not recorded.
+pub(crate) unsafe fn record_module(ctx: *mut JSContext, module_name: &str, m: JSValue) {
+    let mut recorded = RECORDED_MODULES.lock().unwrap();
+    if let Some(modules) = recorded.as_mut() {
+        if !resolver::is_embedded_module(module_name) {
+            if let Some(buf) = write_object(ctx, m) {
+                modules.push((module_name.to_string(), buf));
+            }
+        }
+    }
+}
+
+// layout: MAGIC, module count, then (name length, name, code length, code) per
+// module with all integers as little endian u32. The entry module comes last.
+fn encode(modules: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(&(modules.len() as u32).to_le_bytes());
+    for (name, code) in modules {
+        buf.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(&(code.len() as u32).to_le_bytes());
+        buf.extend_from_slice(code);
+    }
+    buf
+}
+
+fn decode(buf: &[u8]) -> Result<Vec<(&str, &[u8])>, Error> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid bytecode file");
+    fn take<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if buf.len() < len {
+            return None;
+        }
+        let (head, tail) = buf.split_at(len);
+        *buf = tail;
+        Some(head)
+    }
+    fn take_u32(buf: &mut &[u8]) -> Option<usize> {
+        take(buf, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    }
+
+    let mut buf = buf;
+    if take(&mut buf, MAGIC.len()) != Some(MAGIC) {
+        return Err(invalid());
+    }
+    let count = take_u32(&mut buf).ok_or_else(invalid)?;
+    let mut modules = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name_len = take_u32(&mut buf).ok_or_else(invalid)?;
+        let name = take(&mut buf, name_len).ok_or_else(invalid)?;
+        let name = std::str::from_utf8(name).map_err(|_| invalid())?;
+        let code_len = take_u32(&mut buf).ok_or_else(invalid)?;
+        let code = take(&mut buf, code_len).ok_or_else(invalid)?;
+        modules.push((name, code));
+    }
+    Ok(modules)
+}
+
+/// Reads every module of a bundle into `ctx` so later imports find them by name,
+/// and returns the (not yet evaluated) entry module.
+pub(crate) unsafe fn load_module_graph(ctx: *mut JSContext, buf: &[u8]) -> Result<JSValue, Error> {
+    let modules = decode(buf)?;
+    let mut entry = None;
+    for (name, code) in modules {
+        let m = JS_ReadObject(ctx, code.as_ptr(), code.len(), JS_READ_OBJ_BYTECODE as i32);
+        if JS_IsException_real(m) != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("could not read bytecode of module '{}'", name),
+            ));
+        }
+        js_module_set_import_meta(ctx, m, 0, 0);
+        entry = Some(m);
+    }
+    entry.ok_or_else(|| Error::new(ErrorKind::InvalidData, "empty bytecode file"))
+}
+
+impl Context {
+    /// Compiles `filename` and every module it imports from disk into a single
+    /// bytecode bundle that `eval_bytecode` runs without parsing or transpiling.
+    pub fn compile_module_graph(&mut self, filename: &str) -> Result<Vec<u8>, Error> {
+        let code = resolver::import(filename)?;
+        *RECORDED_MODULES.lock().unwrap() = Some(vec![]);
+        let entry = unsafe { self.compile_and_resolve(code, filename) };
+        let mut modules = RECORDED_MODULES.lock().unwrap().take().unwrap_or_default();
+        modules.push((filename.to_string(), entry?));
+        Ok(encode(&modules))
+    }
+
+    unsafe fn compile_and_resolve(
+        &mut self,
+        code: Vec<u8>,
+        filename: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let ctx = self.ctx;
+        let len = code.len();
+        let m = JS_Eval(
+            ctx,
+            make_c_string(code).as_ptr(),
+            len,
+            make_c_string(filename).as_ptr(),
+            (JS_EVAL_TYPE_MODULE | JS_EVAL_FLAG_COMPILE_ONLY) as i32,
+        );
+        // loads (and so records) the whole import graph
+        if JS_IsException_real(m) != 0 || JS_ResolveModule(ctx, m) < 0 {
+            let e = self.take_exception();
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("failed to compile '{}': {:#}", filename, e),
+            ));
+        }
+        write_object(ctx, m).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Other,
+                format!("failed to write bytecode of '{}'", filename),
+            )
+        })
+    }
+
+    /// Runs a bundle produced by `compile_module_graph` as the main module.
+    pub fn eval_bytecode(&mut self, buf: &[u8]) -> Result<JsValue, JsError> {
+        unsafe {
+            let ctx = self.ctx;
+            let val = match load_module_graph(ctx, buf) {
+                Ok(m) => {
+                    if JS_ResolveModule(ctx, m) < 0 {
+                        js_exception()
+                    } else {
+                        js_module_set_import_meta(ctx, m, 0, 1);
+                        JS_EvalFunction(ctx, m)
+                    }
+                }
+                Err(e) => {
+                    self.throw_internal_type_error(&e.to_string());
+                    js_exception()
+                }
+            };
+            if JS_IsException_real(val) > 0 {
+                return Err(self.take_exception());
+            }
+            let val = JsValue::from_qjs_value(ctx, val);
+            self.promise_loop_poll();
+            Ok(val)
+        }
+    }
+}
@@ -453,7 +453,14 @@ unsafe fn gc_mark_value(
         JsValue::String(_) => {}
         JsValue::Object(_) => {}
         JsValue::ArrayBuffer(_) => {}
+        JsValue::TypedArray(_) => {}
+        JsValue::DataView(_) => {}
+        JsValue::Date(_) => {}
+        JsValue::RegExp(_) => {}
+        JsValue::Map(_) => {}
+        JsValue::Set(_) => {}
         JsValue::Function(_) => {}
+        JsValue::Symbol(_) => {}
         _ => return,
     }
     JS_MarkValue(rt, v.get_qjs_value(), mark_func);
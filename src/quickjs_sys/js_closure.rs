@@ -0,0 +1,147 @@
+use super::qjs::*;
+use super::{make_c_string, Context, FromJsArgs, IntoJsValue, JsFunction, JsRef, JsValue};
+
+use std::ops::DerefMut;
+
+type BoxedClosure = Box<dyn Fn(&mut Context, JsValue, &[JsValue]) -> JsValue>;
+
+static mut CLOSURE_CLASS_ID: u32 = 0;
+
+// The closure lives in the opaque of an object of this class, which is kept
+// in the function's data slot. The object has no prototype and never reaches
+// JS; its finalizer drops the closure once the function is collected.
+unsafe extern "C" fn closure_finalizer(_rt: *mut JSRuntime, val: JSValue) {
+    let p = JS_GetOpaque(val, CLOSURE_CLASS_ID) as *mut BoxedClosure;
+    if !p.is_null() {
+        drop(Box::from_raw(p));
+    }
+}
+
+unsafe fn closure_class_id(rt: *mut JSRuntime) -> u32 {
+    if CLOSURE_CLASS_ID == 0 {
+        JS_NewClassID(&mut CLOSURE_CLASS_ID);
+    }
+    if JS_IsRegisteredClass(rt, CLOSURE_CLASS_ID) == 0 {
+        let js_def = JSClassDef {
+            class_name: "RustClosure\0".as_ptr().cast(),
+            finalizer: Some(closure_finalizer),
+            gc_mark: None,
+            call: None,
+            exotic: std::ptr::null_mut(),
+        };
+        JS_NewClass(rt, CLOSURE_CLASS_ID, &js_def);
+    }
+    CLOSURE_CLASS_ID
+}
+
+unsafe extern "C" fn closure_trampoline(
+    ctx: *mut JSContext,
+    this_obj: JSValue,
+    len: ::std::os::raw::c_int,
+    argv: *mut JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut JSValue,
+) -> JSValue {
+    let mut n_ctx = std::mem::ManuallyDrop::new(Context { ctx });
+    let n_ctx = n_ctx.deref_mut();
+
+    let f = JS_GetOpaque(*func_data, CLOSURE_CLASS_ID) as *const BoxedClosure;
+    let f = match f.as_ref() {
+        Some(f) => f,
+        None => {
+            return JsValue::Exception(n_ctx.throw_internal_type_error("closure was dropped"))
+                .into_qjs_value()
+        }
+    };
+
+    let this_obj = JsValue::from_qjs_value(ctx, JS_DupValue_real(ctx, this_obj));
+    let mut arg_vec = vec![];
+    for i in 0..len {
+        let v = *argv.offset(i as isize);
+        arg_vec.push(JsValue::from_qjs_value(ctx, JS_DupValue_real(ctx, v)));
+    }
+    let r = f(n_ctx, this_obj, arg_vec.as_slice());
+    r.into_qjs_value()
+}
+
+impl Context {
+    /// Like `wrap_function`, but `f` may capture state. The captured data is
+    /// dropped when the returned function is garbage collected.
+    ///
+    /// Captured `JsValue`s are not traced by the GC, so they stay alive for as
+    /// long as the function does; a closure that captures its own function
+    /// is never collected.
+    ///
+    /// ```ignore
+    /// let counter = std::rc::Rc::new(std::cell::Cell::new(0));
+    /// let c = counter.clone();
+    /// let f = ctx.new_closure("count", move |_ctx, _this, _argv| {
+    ///     c.set(c.get() + 1);
+    ///     JsValue::Int(c.get())
+    /// });
+    /// ```
+    pub fn new_closure<F>(&mut self, name: &str, f: F) -> JsFunction
+    where
+        F: Fn(&mut Context, JsValue, &[JsValue]) -> JsValue + 'static,
+    {
+        self.new_closure_with_length(name, 1, Box::new(f))
+    }
+
+    /// The closure counterpart of `wrap_typed_function`.
+    pub fn new_typed_closure<A, R, F>(&mut self, name: &str, f: F) -> JsFunction
+    where
+        A: FromJsArgs,
+        R: IntoJsValue,
+        F: Fn(&mut Context, A) -> R + 'static,
+    {
+        self.new_closure_with_length(
+            name,
+            A::LENGTH,
+            Box::new(move |ctx, _this, argv| match A::from_js_args(ctx, argv) {
+                Ok(args) => f(ctx, args).into_js_value(ctx),
+                Err(e) => Err::<(), _>(e).into_js_value(ctx),
+            }),
+        )
+    }
+
+    pub(super) fn new_closure_with_length(
+        &mut self,
+        name: &str,
+        length: i32,
+        f: BoxedClosure,
+    ) -> JsFunction {
+        unsafe {
+            let class_id = closure_class_id(self.rt());
+            let holder = JS_NewObjectClass(self.ctx, class_id as i32);
+            if JS_IsException_real(holder) != 0 {
+                return JsFunction(JsRef {
+                    ctx: self.ctx,
+                    v: holder,
+                });
+            }
+            JS_SetOpaque(holder, Box::into_raw(Box::new(f)).cast());
+
+            let mut data = [holder];
+            let v = JS_NewCFunctionData(
+                self.ctx,
+                Some(closure_trampoline),
+                length,
+                0,
+                1,
+                data.as_mut_ptr(),
+            );
+            // the function holds its own reference to `holder` now
+            JS_FreeValue_real(self.ctx, holder);
+
+            let name_value = JS_NewStringLen(self.ctx, name.as_ptr().cast(), name.len());
+            JS_DefinePropertyValueStr(
+                self.ctx,
+                v,
+                make_c_string("name").as_ptr(),
+                name_value,
+                JS_PROP_CONFIGURABLE as i32,
+            );
+            JsFunction(JsRef { ctx: self.ctx, v })
+        }
+    }
+}
@@ -0,0 +1,571 @@
+use super::qjs::*;
+use super::{
+    AsObject, Context, JsArray, JsArrayBuffer, JsBigNum, JsDataView, JsDate, JsException,
+    JsFunction, JsMap, JsObject, JsRef, JsRegExp, JsSet, JsString, JsSymbol, JsTypedArray, JsValue,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::DerefMut;
+
+pub use drop_derive::{FromJsValue, IntoJsValue};
+
+/// Returned when a `JsValue` does not have the shape a `FromJsValue` impl
+/// expects. `path` says where in the value the mismatch is, e.g.
+/// `argument 1.options.mode`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertError {
+    pub expected: &'static str,
+    pub found: &'static str,
+    pub path: String,
+}
+
+impl ConvertError {
+    pub fn new(expected: &'static str, value: &JsValue) -> Self {
+        ConvertError {
+            expected,
+            found: value.type_name(),
+            path: String::new(),
+        }
+    }
+
+    /// Prefixes the path with a field name, or an index like `[3]`.
+    pub fn within(mut self, name: &str) -> Self {
+        self.path = if self.path.is_empty() {
+            name.to_string()
+        } else if self.path.starts_with('[') {
+            format!("{}{}", name, self.path)
+        } else {
+            format!("{}.{}", name, self.path)
+        };
+        self
+    }
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "expected {}, got {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+impl JsValue {
+    /// The name used for this value in conversion errors. Close to `typeof`,
+    /// but tells arrays, `null`, buffers and the other builtins apart from
+    /// plain objects.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            JsValue::Int(_) | JsValue::Float(_) => "number",
+            JsValue::BigNum(_) => "bigint",
+            JsValue::String(_) => "string",
+            JsValue::Module(_) => "module",
+            JsValue::Object(_) | JsValue::Other(_) => "object",
+            JsValue::Array(_) => "array",
+            JsValue::Promise(_) => "promise",
+            JsValue::ArrayBuffer(_) => "ArrayBuffer",
+            JsValue::TypedArray(array) => array.kind().name(),
+            JsValue::DataView(_) => "DataView",
+            JsValue::Date(_) => "Date",
+            JsValue::RegExp(_) => "RegExp",
+            JsValue::Map(_) => "Map",
+            JsValue::Set(_) => "Set",
+            JsValue::Function(_) => "function",
+            JsValue::Symbol(_) => "symbol",
+            JsValue::Bool(_) => "boolean",
+            JsValue::Null => "null",
+            JsValue::UnDefined => "undefined",
+            JsValue::Exception(_) => "exception",
+            JsValue::FunctionByteCode(_) => "bytecode",
+        }
+    }
+}
+
+/// Conversion from a JS value into a Rust value. Derivable for structs with
+/// named fields, which are read from the properties of the same name.
+pub trait FromJsValue: Sized {
+    fn from_js_value(ctx: &mut Context, value: &JsValue) -> Result<Self, ConvertError>;
+}
+
+/// Conversion from a Rust value into a JS value. Derivable for structs with
+/// named fields, which become plain objects.
+pub trait IntoJsValue {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue;
+}
+
+impl FromJsValue for JsValue {
+    fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoJsValue for JsValue {
+    fn into_js_value(self, _: &mut Context) -> JsValue {
+        self
+    }
+}
+
+macro_rules! impl_js_ref_variant {
+    ($t:ident, $variant:ident, $name:expr) => {
+        impl FromJsValue for $t {
+            fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+                match value {
+                    JsValue::$variant(v) => Ok(v.clone()),
+                    _ => Err(ConvertError::new($name, value)),
+                }
+            }
+        }
+
+        impl IntoJsValue for $t {
+            fn into_js_value(self, _: &mut Context) -> JsValue {
+                JsValue::$variant(self)
+            }
+        }
+    };
+}
+
+impl_js_ref_variant!(JsObject, Object, "object");
+impl_js_ref_variant!(JsArray, Array, "array");
+impl_js_ref_variant!(JsFunction, Function, "function");
+impl_js_ref_variant!(JsString, String, "string");
+impl_js_ref_variant!(JsArrayBuffer, ArrayBuffer, "ArrayBuffer");
+impl_js_ref_variant!(JsTypedArray, TypedArray, "typed array");
+impl_js_ref_variant!(JsDataView, DataView, "DataView");
+impl_js_ref_variant!(JsBigNum, BigNum, "bigint");
+impl_js_ref_variant!(JsDate, Date, "Date");
+impl_js_ref_variant!(JsRegExp, RegExp, "RegExp");
+impl_js_ref_variant!(JsMap, Map, "Map");
+impl_js_ref_variant!(JsSet, Set, "Set");
+impl_js_ref_variant!(JsSymbol, Symbol, "symbol");
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromJsValue for $t {
+                fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+                    let n = match value {
+                        JsValue::Int(n) => <$t>::try_from(*n).ok(),
+                        // `MAX + 1` is a power of two and exact as f64, while
+                        // `MAX as f64` already rounds up to it for 64 bits
+                        JsValue::Float(f)
+                            if f.fract() == 0.0
+                                && *f >= <$t>::MIN as f64
+                                && *f < <$t>::MAX as f64 + 1.0 =>
+                        {
+                            Some(*f as $t)
+                        }
+                        JsValue::BigNum(n) => n.to_i128().and_then(|n| <$t>::try_from(n).ok()),
+                        _ => None,
+                    };
+                    n.ok_or_else(|| ConvertError::new("integer", value))
+                }
+            }
+
+            impl IntoJsValue for $t {
+                fn into_js_value(self, _: &mut Context) -> JsValue {
+                    match i32::try_from(self) {
+                        Ok(n) => JsValue::Int(n),
+                        Err(_) => JsValue::Float(self as f64),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {
+        $(
+            impl FromJsValue for $t {
+                fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+                    match value {
+                        JsValue::Int(n) => Ok(*n as $t),
+                        JsValue::Float(f) => Ok(*f as $t),
+                        _ => Err(ConvertError::new("number", value)),
+                    }
+                }
+            }
+
+            impl IntoJsValue for $t {
+                fn into_js_value(self, _: &mut Context) -> JsValue {
+                    JsValue::Float(self as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_float!(f32, f64);
+
+impl FromJsValue for bool {
+    fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        match value {
+            JsValue::Bool(b) => Ok(*b),
+            _ => Err(ConvertError::new("boolean", value)),
+        }
+    }
+}
+
+impl IntoJsValue for bool {
+    fn into_js_value(self, _: &mut Context) -> JsValue {
+        JsValue::Bool(self)
+    }
+}
+
+impl FromJsValue for String {
+    fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        match value {
+            JsValue::String(s) => Ok(s.to_string()),
+            _ => Err(ConvertError::new("string", value)),
+        }
+    }
+}
+
+impl IntoJsValue for String {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_string(&self).into()
+    }
+}
+
+impl IntoJsValue for &str {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_string(self).into()
+    }
+}
+
+impl IntoJsValue for () {
+    fn into_js_value(self, _: &mut Context) -> JsValue {
+        JsValue::UnDefined
+    }
+}
+
+/// `undefined` and `null` are `None`, and `None` is `undefined`.
+impl<T: FromJsValue> FromJsValue for Option<T> {
+    fn from_js_value(ctx: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        match value {
+            JsValue::UnDefined | JsValue::Null => Ok(None),
+            _ => T::from_js_value(ctx, value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoJsValue> IntoJsValue for Option<T> {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        match self {
+            Some(v) => v.into_js_value(ctx),
+            None => JsValue::UnDefined,
+        }
+    }
+}
+
+impl<T: FromJsValue> FromJsValue for Vec<T> {
+    fn from_js_value(ctx: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        let items = match value {
+            JsValue::Array(array) => array
+                .to_vec()
+                .map_err(|_| ConvertError::new("array", value))?,
+            _ => return Err(ConvertError::new("array", value)),
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| T::from_js_value(ctx, item).map_err(|e| e.within(&format!("[{}]", i))))
+            .collect()
+    }
+}
+
+impl<T: IntoJsValue> IntoJsValue for Vec<T> {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        let mut array = ctx.new_array();
+        for (i, item) in self.into_iter().enumerate() {
+            let item = item.into_js_value(ctx);
+            array.put(i, item);
+        }
+        array.into()
+    }
+}
+
+/// Reads the own enumerable string-keyed properties of an object, or the
+/// entries of a `Map` with string keys.
+impl<T: FromJsValue> FromJsValue for HashMap<String, T> {
+    fn from_js_value(ctx: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        let properties = match value {
+            JsValue::Object(obj) => obj
+                .to_map()
+                .map_err(|_| ConvertError::new("object", value))?,
+            JsValue::Map(map) => {
+                let entries = map.entries().map_err(|_| ConvertError::new("Map", value))?;
+                let mut properties = HashMap::with_capacity(entries.len());
+                for (key, item) in entries {
+                    let key = String::from_js_value(ctx, &key).map_err(|e| e.within("<key>"))?;
+                    properties.insert(key, item);
+                }
+                properties
+            }
+            _ => return Err(ConvertError::new("object", value)),
+        };
+        properties
+            .into_iter()
+            .map(|(key, item)| {
+                let item = T::from_js_value(ctx, &item).map_err(|e| e.within(&key))?;
+                Ok((key, item))
+            })
+            .collect()
+    }
+}
+
+impl<T: IntoJsValue> IntoJsValue for HashMap<String, T> {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        let mut obj = ctx.new_object();
+        for (key, item) in self {
+            let item = item.into_js_value(ctx);
+            obj.set(&key, item);
+        }
+        obj.into()
+    }
+}
+
+/// Reads a `Set` or an array.
+impl<T: FromJsValue + Eq + Hash> FromJsValue for HashSet<T> {
+    fn from_js_value(ctx: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        let items = match value {
+            JsValue::Set(set) => set.values().map_err(|_| ConvertError::new("Set", value))?,
+            JsValue::Array(array) => array
+                .to_vec()
+                .map_err(|_| ConvertError::new("Set", value))?,
+            _ => return Err(ConvertError::new("Set", value)),
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| T::from_js_value(ctx, item).map_err(|e| e.within(&format!("[{}]", i))))
+            .collect()
+    }
+}
+
+impl<T: IntoJsValue> IntoJsValue for HashSet<T> {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_set_from(self).into()
+    }
+}
+
+/// `Err` is thrown, so a native function returning `Result` throws the error
+/// in JS. A `JsException` is passed on as it is already pending.
+impl<T: IntoJsValue, E: IntoJsValue> IntoJsValue for Result<T, E> {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        match self {
+            Ok(v) => v.into_js_value(ctx),
+            Err(e) => match e.into_js_value(ctx) {
+                e @ JsValue::Exception(_) => e,
+                e => ctx.throw_error(e).into(),
+            },
+        }
+    }
+}
+
+impl IntoJsValue for JsException {
+    fn into_js_value(self, _: &mut Context) -> JsValue {
+        JsValue::Exception(self)
+    }
+}
+
+impl IntoJsValue for std::io::Error {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_error(&self.to_string())
+    }
+}
+
+/// A `TypeError` object, not thrown.
+impl IntoJsValue for ConvertError {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.throw_type_error(&self.to_string());
+        unsafe { JsValue::from_qjs_value(ctx.ctx, JS_GetException(ctx.ctx)) }
+    }
+}
+
+/// The contents of an `ArrayBuffer`, a typed array or a `DataView`, copied out
+/// of the JS heap. Converts back into a new `Uint8Array`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bytes(pub Vec<u8>);
+
+impl From<Vec<u8>> for Bytes {
+    fn from(v: Vec<u8>) -> Self {
+        Bytes(v)
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(b: Bytes) -> Self {
+        b.0
+    }
+}
+
+impl std::ops::Deref for Bytes {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Bytes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl FromJsValue for Bytes {
+    fn from_js_value(_: &mut Context, value: &JsValue) -> Result<Self, ConvertError> {
+        value
+            .as_bytes()
+            .map(|bytes| Bytes(bytes.to_vec()))
+            .ok_or_else(|| ConvertError::new("ArrayBuffer or typed array", value))
+    }
+}
+
+impl IntoJsValue for Bytes {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.new_uint8_array(&self.0).into()
+    }
+}
+
+/// Collects the arguments that are left over, for functions like
+/// `setTimeout(callback, delay, ...args)`. Only meaningful as the last element
+/// of a `FromJsArgs` tuple.
+#[derive(Debug, Clone, Default)]
+pub struct Rest<T>(pub Vec<T>);
+
+/// One element of a `FromJsArgs` tuple. Implemented for every `FromJsValue`,
+/// where a missing argument reads as `undefined`, and for `Rest`.
+pub trait FromJsArg: Sized {
+    fn from_js_arg(ctx: &mut Context, argv: &[JsValue], index: usize)
+        -> Result<Self, ConvertError>;
+}
+
+impl<T: FromJsValue> FromJsArg for T {
+    fn from_js_arg(
+        ctx: &mut Context,
+        argv: &[JsValue],
+        index: usize,
+    ) -> Result<Self, ConvertError> {
+        let value = argv.get(index).cloned().unwrap_or(JsValue::UnDefined);
+        T::from_js_value(ctx, &value).map_err(|e| e.within(&format!("argument {}", index + 1)))
+    }
+}
+
+impl<T: FromJsValue> FromJsArg for Rest<T> {
+    fn from_js_arg(
+        ctx: &mut Context,
+        argv: &[JsValue],
+        index: usize,
+    ) -> Result<Self, ConvertError> {
+        let rest = argv.get(index..).unwrap_or(&[]);
+        let mut items = Vec::with_capacity(rest.len());
+        for i in 0..rest.len() {
+            items.push(T::from_js_arg(ctx, argv, index + i)?);
+        }
+        Ok(Rest(items))
+    }
+}
+
+/// The argument list of a typed native function, a tuple of `FromJsArg`.
+pub trait FromJsArgs: Sized {
+    /// The `length` of the JS function.
+    const LENGTH: i32;
+
+    fn from_js_args(ctx: &mut Context, argv: &[JsValue]) -> Result<Self, ConvertError>;
+}
+
+macro_rules! impl_from_js_args {
+    ($len:expr; $($t:ident $i:tt),*) => {
+        impl<$($t: FromJsArg),*> FromJsArgs for ($($t,)*) {
+            const LENGTH: i32 = $len;
+
+            #[allow(unused_variables)]
+            fn from_js_args(ctx: &mut Context, argv: &[JsValue]) -> Result<Self, ConvertError> {
+                Ok(($($t::from_js_arg(ctx, argv, $i)?,)*))
+            }
+        }
+    };
+}
+
+impl_from_js_args!(0;);
+impl_from_js_args!(1; A 0);
+impl_from_js_args!(2; A 0, B 1);
+impl_from_js_args!(3; A 0, B 1, C 2);
+impl_from_js_args!(4; A 0, B 1, C 2, D 3);
+impl_from_js_args!(5; A 0, B 1, C 2, D 3, E 4);
+impl_from_js_args!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_js_args!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_js_args!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+struct JsTypedFunctionTrampoline;
+impl JsTypedFunctionTrampoline {
+    unsafe extern "C" fn callback<A, R, F>(
+        ctx: *mut JSContext,
+        _this_obj: JSValue,
+        len: ::std::os::raw::c_int,
+        argv: *mut JSValue,
+    ) -> JSValue
+    where
+        A: FromJsArgs,
+        R: IntoJsValue,
+        F: Fn(&mut Context, A) -> R,
+    {
+        let mut n_ctx = std::mem::ManuallyDrop::new(Context { ctx });
+        let n_ctx = n_ctx.deref_mut();
+        let mut arg_vec = vec![];
+        for i in 0..len {
+            let v = *argv.offset(i as isize);
+            arg_vec.push(JsValue::from_qjs_value(ctx, JS_DupValue_real(ctx, v)));
+        }
+        let r = match A::from_js_args(n_ctx, arg_vec.as_slice()) {
+            Ok(args) => {
+                let f = std::mem::zeroed::<F>();
+                f(n_ctx, args).into_js_value(n_ctx)
+            }
+            Err(e) => Err::<(), _>(e).into_js_value(n_ctx),
+        };
+        r.into_qjs_value()
+    }
+}
+
+impl Context {
+    /// Like `wrap_function`, but the arguments are converted with `FromJsArgs`
+    /// and the result with `IntoJsValue`. Arguments of the wrong type throw a
+    /// `TypeError` naming the argument instead of reaching `f`.
+    ///
+    /// ```ignore
+    /// fn repeat(_ctx: &mut Context, (s, n): (String, usize)) -> String {
+    ///     s.repeat(n)
+    /// }
+    /// let f = ctx.wrap_typed_function("repeat", repeat);
+    /// ```
+    pub fn wrap_typed_function<A, R, F>(&mut self, name: &str, _: F) -> JsFunction
+    where
+        A: FromJsArgs,
+        R: IntoJsValue,
+        F: Fn(&mut Context, A) -> R,
+    {
+        unsafe {
+            assert_size_zero!(@typed, A, R, F);
+
+            let name = super::make_c_string(name);
+            let v = JS_NewCFunction_real(
+                self.ctx,
+                Some(JsTypedFunctionTrampoline::callback::<A, R, F>),
+                name.as_ptr(),
+                A::LENGTH,
+            );
+            JsFunction(JsRef { ctx: self.ctx, v })
+        }
+    }
+}
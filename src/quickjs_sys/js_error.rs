@@ -0,0 +1,129 @@
+use super::qjs::*;
+use super::{source_map, AsObject, Context, JsValue};
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsErrorKind {
+    /// A value thrown by JS and not caught.
+    Exception,
+    /// The runtime was interrupted or ran past its deadline, see
+    /// `Runtime::interrupt_handle`.
+    Interrupted,
+    /// A file recorded by `EventLoop::watch_loaded_files` changed.
+    WatchedFileChanged,
+}
+
+/// An error returned by `eval_*` and `js_loop` instead of being printed.
+///
+/// For a thrown `Error` the fields are read from its properties, `properties`
+/// holding the own enumerable ones besides `name`, `message` and `stack`
+/// (e.g. `code`). For any other thrown value `name` is empty and `message` is
+/// the value converted to a string.
+///
+/// `Display` shows `name: message`, the alternate form `{:#}` appends the
+/// stack like `js_std_dump_error` does. Frames in transpiled modules point at
+/// the original sources, see `source_map::rewrite_stack`.
+#[derive(Debug, Clone)]
+pub struct JsError {
+    pub kind: JsErrorKind,
+    pub name: String,
+    pub message: String,
+    pub stack: Option<String>,
+    pub properties: HashMap<String, JsValue>,
+    /// The thrown value, to rethrow it with `Context::throw_error`.
+    pub value: JsValue,
+}
+
+fn string_property(ctx: &mut Context, value: &JsValue, key: &str) -> Option<String> {
+    match value.get(key)? {
+        JsValue::UnDefined => None,
+        JsValue::String(s) => Some(s.to_string()),
+        v => match ctx.value_to_string(&v) {
+            JsValue::String(s) => Some(s.to_string()),
+            _ => None,
+        },
+    }
+}
+
+impl JsError {
+    pub fn new(kind: JsErrorKind, message: &str) -> Self {
+        JsError {
+            kind,
+            name: String::new(),
+            message: message.to_string(),
+            stack: None,
+            properties: HashMap::new(),
+            value: JsValue::UnDefined,
+        }
+    }
+
+    /// Describes a thrown `value`.
+    pub fn from_value(ctx: &mut Context, value: JsValue) -> Self {
+        let kind = if ctx.is_interrupted() {
+            JsErrorKind::Interrupted
+        } else {
+            JsErrorKind::Exception
+        };
+        match &value {
+            JsValue::Object(obj) => {
+                let mut properties = obj.to_map().unwrap_or_default();
+                for key in &["name", "message", "stack"] {
+                    properties.remove(*key);
+                }
+                let stack = string_property(ctx, &value, "stack")
+                    .map(|stack| source_map::rewrite_stack(&stack));
+                JsError {
+                    kind,
+                    name: string_property(ctx, &value, "name").unwrap_or_default(),
+                    message: string_property(ctx, &value, "message").unwrap_or_default(),
+                    stack,
+                    properties,
+                    value,
+                }
+            }
+            _ => {
+                let message = match ctx.value_to_string(&value) {
+                    JsValue::String(s) => s.to_string(),
+                    _ => String::new(),
+                };
+                JsError {
+                    kind,
+                    name: String::new(),
+                    message,
+                    stack: None,
+                    properties: HashMap::new(),
+                    value,
+                }
+            }
+        }
+    }
+}
+
+impl Display for JsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}", self.message)?;
+        } else {
+            write!(f, "{}: {}", self.name, self.message)?;
+        }
+        if f.alternate() {
+            if let Some(stack) = &self.stack {
+                write!(f, "\n{}", stack.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JsError {}
+
+impl Context {
+    /// Takes the pending exception out of the context, e.g. after a
+    /// `JsValue::Exception` was returned.
+    pub fn take_exception(&mut self) -> JsError {
+        let value = unsafe { JsValue::from_qjs_value(self.ctx, JS_GetException(self.ctx)) };
+        JsError::from_value(self, value)
+    }
+}
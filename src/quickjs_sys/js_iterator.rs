@@ -0,0 +1,273 @@
+use super::qjs::*;
+use super::{
+    AsObject, Context, IntoJsValue, JsException, JsFunction, JsObject, JsRef, JsSymbol, JsValue,
+    PropertyFlags, WellKnownSymbol,
+};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// `value[key]`, which unlike `AsObject::get` also works on primitives such as
+// strings
+fn get_property(ctx: *mut JSContext, value: &JsValue, key: &str) -> JsValue {
+    unsafe {
+        let v = value.get_qjs_value();
+        let r = JS_GetPropertyStr(ctx, v, super::make_c_string(key).as_ptr().cast());
+        JsValue::from_qjs_value(ctx, r)
+    }
+}
+
+fn get_symbol_property(ctx: *mut JSContext, value: &JsValue, key: &JsSymbol) -> JsValue {
+    unsafe {
+        let v = value.get_qjs_value();
+        let atom = key.to_atom();
+        let r = JS_GetPropertyInternal(ctx, v, atom, v, 0);
+        JS_FreeAtom(ctx, atom);
+        JsValue::from_qjs_value(ctx, r)
+    }
+}
+
+fn is_object(value: &JsValue) -> bool {
+    unsafe { JS_IsObject_real(value.get_qjs_value()) != 0 }
+}
+
+fn is_truthy(ctx: *mut JSContext, value: &JsValue) -> bool {
+    unsafe { JS_ToBool(ctx, value.get_qjs_value()) != 0 }
+}
+
+// the thrown value itself, which a rejection reason is compared with
+fn take_exception_value(ctx: *mut JSContext) -> JsValue {
+    unsafe { JsValue::from_qjs_value(ctx, JS_GetException(ctx)) }
+}
+
+/// An iterator object and its `next` method, closed with `return()` when it
+/// is dropped before it is done.
+struct IteratorRecord {
+    ctx: *mut JSContext,
+    iterator: JsValue,
+    next: JsFunction,
+    done: bool,
+}
+
+impl IteratorRecord {
+    fn open(ctx: &mut Context, iterable: &JsValue, method: JsValue) -> Result<Self, JsException> {
+        let method = match method {
+            JsValue::Function(method) => method,
+            JsValue::Exception(e) => return Err(e),
+            _ => {
+                let msg = format!("{} is not iterable", iterable.type_name());
+                return Err(ctx.throw_type_error(&msg));
+            }
+        };
+        let iterator = match method.call_with_this(iterable, &[]) {
+            JsValue::Exception(e) => return Err(e),
+            iterator if is_object(&iterator) => iterator,
+            _ => return Err(ctx.throw_type_error("iterator is not an object")),
+        };
+        let next = match get_property(ctx.ctx, &iterator, "next") {
+            JsValue::Function(next) => next,
+            JsValue::Exception(e) => return Err(e),
+            _ => return Err(ctx.throw_type_error("iterator.next is not a function")),
+        };
+        Ok(IteratorRecord {
+            ctx: ctx.ctx,
+            iterator,
+            next,
+            done: false,
+        })
+    }
+
+    fn call_next(&self) -> JsValue {
+        self.next.call_with_this(&self.iterator, &[])
+    }
+
+    // the value of an iterator result, `None` once it says it is done
+    fn complete(&mut self, result: JsValue) -> Option<Result<JsValue, JsException>> {
+        let mut ctx = std::mem::ManuallyDrop::new(Context { ctx: self.ctx });
+        if let JsValue::Exception(e) = result {
+            self.done = true;
+            return Some(Err(e));
+        }
+        if !is_object(&result) {
+            self.done = true;
+            let e = ctx.throw_type_error("iterator result is not an object");
+            return Some(Err(e));
+        }
+        match get_property(self.ctx, &result, "done") {
+            JsValue::Exception(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            done if is_truthy(self.ctx, &done) => {
+                self.done = true;
+                None
+            }
+            _ => match get_property(self.ctx, &result, "value") {
+                JsValue::Exception(e) => {
+                    self.done = true;
+                    Some(Err(e))
+                }
+                value => Some(Ok(value)),
+            },
+        }
+    }
+}
+
+impl Drop for IteratorRecord {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        if let JsValue::Function(f) = get_property(self.ctx, &self.iterator, "return") {
+            // like a `break` out of `for...of`, errors of `return()` are lost
+            if let JsValue::Exception(_) = f.call_with_this(&self.iterator, &[]) {
+                take_exception_value(self.ctx);
+            }
+        }
+    }
+}
+
+/// Drives the `Symbol.iterator` protocol of a JS value, made with
+/// `Context::get_iterator`. An `Err` ends the iteration, and dropping it early
+/// calls the iterator's `return()` like `break` in a `for...of` loop.
+pub struct JsIterator(IteratorRecord);
+
+impl Iterator for JsIterator {
+    type Item = Result<JsValue, JsException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.done {
+            return None;
+        }
+        let result = self.0.call_next();
+        self.0.complete(result)
+    }
+}
+
+/// Drives the `Symbol.asyncIterator` protocol of a JS value, made with
+/// `Context::get_async_iterator`. Values of a sync iterable are awaited like
+/// `for await...of` does.
+///
+/// The promises settle as the event loop runs the promise jobs, so `next` is
+/// meant to be awaited in a future passed to `EventLoop::spawn_local`.
+pub struct JsAsyncIterator {
+    record: IteratorRecord,
+    from_sync: bool,
+}
+
+impl JsAsyncIterator {
+    /// The next value, `None` once the iterator is done. `Err` holds the
+    /// thrown value or the rejection reason, and ends the iteration.
+    pub async fn next(&mut self) -> Option<Result<JsValue, JsValue>> {
+        if self.record.done {
+            return None;
+        }
+        let result = match self.record.call_next() {
+            JsValue::Promise(promise) if !self.from_sync => match promise.into_future().await {
+                Ok(result) => result,
+                Err(reason) => {
+                    self.record.done = true;
+                    return Some(Err(reason));
+                }
+            },
+            result => result,
+        };
+        match self.record.complete(result)? {
+            Err(_) => Some(Err(take_exception_value(self.record.ctx))),
+            Ok(JsValue::Promise(promise)) if self.from_sync => {
+                let value = promise.into_future().await;
+                if value.is_err() {
+                    self.record.done = true;
+                }
+                Some(value)
+            }
+            Ok(value) => Some(Ok(value)),
+        }
+    }
+}
+
+impl Context {
+    /// `iterable[Symbol.iterator]()`, for arrays, strings, `Map`s, `Set`s,
+    /// generators and any other iterable. Throws a `TypeError` for a value
+    /// that is not iterable.
+    pub fn get_iterator(&mut self, iterable: &JsValue) -> Result<JsIterator, JsException> {
+        let symbol = self.well_known_symbol(WellKnownSymbol::Iterator);
+        let method = get_symbol_property(self.ctx, iterable, &symbol);
+        IteratorRecord::open(self, iterable, method).map(JsIterator)
+    }
+
+    /// `iterable[Symbol.asyncIterator]()`, or its `Symbol.iterator` if it has
+    /// none.
+    pub fn get_async_iterator(
+        &mut self,
+        iterable: &JsValue,
+    ) -> Result<JsAsyncIterator, JsException> {
+        let symbol = self.well_known_symbol(WellKnownSymbol::AsyncIterator);
+        match get_symbol_property(self.ctx, iterable, &symbol) {
+            JsValue::UnDefined | JsValue::Null => Ok(JsAsyncIterator {
+                record: self.get_iterator(iterable)?.0,
+                from_sync: true,
+            }),
+            method => Ok(JsAsyncIterator {
+                record: IteratorRecord::open(self, iterable, method)?,
+                from_sync: false,
+            }),
+        }
+    }
+
+    /// A JS iterator that pulls the items of `iter` one at a time as `next()`
+    /// is called, instead of collecting them into an array up front. It
+    /// inherits from `%IteratorPrototype%`, so it is iterable itself and
+    /// works with `for...of` and spreading.
+    ///
+    /// An item that throws, like an `Err` of a `Result`, is thrown from
+    /// `next()` and ends the iteration.
+    pub fn new_iterator<I, T>(&mut self, iter: I) -> JsObject
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'static,
+        T: IntoJsValue,
+    {
+        let items = Rc::new(RefCell::new(Some(iter.into_iter())));
+        let mut obj = unsafe {
+            let proto = JS_GetIteratorPrototype_real(self.ctx);
+            let v = JS_NewObjectProto(self.ctx, proto);
+            JS_FreeValue_real(self.ctx, proto);
+            JsObject(JsRef { ctx: self.ctx, v })
+        };
+
+        let next_items = items.clone();
+        let next = self.new_closure("next", move |ctx, _this, _argv| {
+            let item = next_items.borrow_mut().as_mut().and_then(Iterator::next);
+            let mut result = ctx.new_object();
+            match item {
+                Some(item) => {
+                    let value = item.into_js_value(ctx);
+                    if let JsValue::Exception(_) = value {
+                        next_items.borrow_mut().take();
+                        return value;
+                    }
+                    result.set("value", value);
+                    result.set("done", false.into());
+                }
+                None => {
+                    next_items.borrow_mut().take();
+                    result.set("value", JsValue::UnDefined);
+                    result.set("done", true.into());
+                }
+            }
+            result.into()
+        });
+        let return_ = self.new_closure("return", move |ctx, _this, argv| {
+            items.borrow_mut().take();
+            let mut result = ctx.new_object();
+            result.set("value", argv.get(0).cloned().unwrap_or(JsValue::UnDefined));
+            result.set("done", true.into());
+            result.into()
+        });
+        // hidden like the methods of the builtin iterators
+        obj.define_property("next", next.into(), PropertyFlags::HIDDEN);
+        obj.define_property("return", return_.into(), PropertyFlags::HIDDEN);
+        obj
+    }
+}
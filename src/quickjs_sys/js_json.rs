@@ -0,0 +1,298 @@
+use super::qjs::*;
+#[cfg(feature = "serde_json")]
+use super::{AsObject, DroppableValue, IntoJsValue, JsRef, PropertyFlags};
+use super::{Context, JsException, JsValue};
+
+#[cfg(feature = "serde_json")]
+use serde_json::{Map, Number, Value};
+#[cfg(feature = "serde_json")]
+use std::convert::TryFrom;
+
+impl Context {
+    /// `JSON.parse(json)`, without building a JS string first. Invalid JSON
+    /// throws a `SyntaxError` telling where parsing stopped, which is
+    /// returned as `JsValue::Exception` for `take_exception` to report.
+    pub fn parse_json(&mut self, json: &str) -> JsValue {
+        // the parser looks for a NUL past the end
+        let mut buf = Vec::with_capacity(json.len() + 1);
+        buf.extend_from_slice(json.as_bytes());
+        buf.push(0);
+        unsafe {
+            let v = JS_ParseJSON(
+                self.ctx,
+                buf.as_ptr().cast(),
+                json.len(),
+                "<json>\0".as_ptr().cast(),
+            );
+            JsValue::from_qjs_value(self.ctx, v)
+        }
+    }
+
+    /// `JSON.stringify(value, null, indent)`: nested values are indented by
+    /// `indent` spaces, at most 10, and nothing is indented for 0.
+    ///
+    /// Throws a `TypeError` for cycles and BigInts, as `JSON.stringify` does,
+    /// and for what it returns `undefined` for, like `undefined` itself and
+    /// functions.
+    pub fn to_json(&mut self, value: &JsValue, indent: usize) -> Result<String, JsException> {
+        let json = unsafe {
+            let space = JS_NewInt32_real(self.ctx, indent.min(10) as i32);
+            let v = JS_JSONStringify(self.ctx, value.get_qjs_value(), js_undefined(), space);
+            JsValue::from_qjs_value(self.ctx, v)
+        };
+        match json {
+            JsValue::String(s) => Ok(s.to_string()),
+            JsValue::Exception(e) => Err(e),
+            _ => {
+                let msg = format!("{} can't be converted to JSON", value.type_name());
+                Err(self.throw_type_error(&msg))
+            }
+        }
+    }
+}
+
+/// A bridge to `serde_json::Value`, behind the `serde_json` feature.
+#[cfg(feature = "serde_json")]
+impl Context {
+    /// A JS value built straight from `value`, the same `parse_json` gives
+    /// for its text.
+    pub fn json_value_to_js(&mut self, value: &Value) -> JsValue {
+        match value {
+            Value::Null => JsValue::Null,
+            Value::Bool(b) => JsValue::Bool(*b),
+            Value::Number(n) => number_to_js(n),
+            Value::String(s) => self.new_string(s).into(),
+            Value::Array(items) => {
+                let mut array = self.new_array();
+                for (i, item) in items.iter().enumerate() {
+                    let item = self.json_value_to_js(item);
+                    array.put(i, item);
+                }
+                array.into()
+            }
+            Value::Object(entries) => {
+                let mut obj = self.new_object();
+                for (key, value) in entries {
+                    let value = self.json_value_to_js(value);
+                    // defined rather than assigned, so a "__proto__" key is an
+                    // own property like with `JSON.parse`
+                    obj.define_property(key, value, PropertyFlags::DEFAULT);
+                }
+                obj.into()
+            }
+        }
+    }
+
+    /// `value` as `JSON.stringify` sees it, so `toJSON` methods are called
+    /// and `undefined` properties left out, walked without going through a
+    /// string. Throws like `to_json`.
+    pub fn js_to_json_value(&mut self, value: &JsValue) -> Result<Value, JsException> {
+        let mut walker = JsonWalker {
+            ctx: self,
+            stack: Vec::new(),
+        };
+        match walker.walk("", value.clone())? {
+            Some(v) => Ok(v),
+            None => {
+                let msg = format!("{} can't be converted to JSON", value.type_name());
+                Err(self.throw_type_error(&msg))
+            }
+        }
+    }
+}
+
+// `JSON.stringify` nests no deeper than the JS stack lets it
+#[cfg(feature = "serde_json")]
+const MAX_DEPTH: usize = 2048;
+
+#[cfg(feature = "serde_json")]
+struct JsonWalker<'a> {
+    ctx: &'a mut Context,
+    // the objects being walked, to catch cycles
+    stack: Vec<usize>,
+}
+
+#[cfg(feature = "serde_json")]
+impl JsonWalker<'_> {
+    // `None` for what `JSON.stringify` leaves out: `undefined`, functions
+    // and symbols
+    fn walk(&mut self, key: &str, value: JsValue) -> Result<Option<Value>, JsException> {
+        let value = self.call_to_json(key, value)?;
+        match value {
+            JsValue::Null => Ok(Some(Value::Null)),
+            JsValue::Bool(b) => Ok(Some(Value::Bool(b))),
+            JsValue::Int(n) => Ok(Some(Value::from(n))),
+            JsValue::Float(n) => Ok(Some(float_to_json(n))),
+            JsValue::String(s) => Ok(Some(Value::String(s.to_string()))),
+            JsValue::UnDefined | JsValue::Function(_) | JsValue::Symbol(_) => Ok(None),
+            JsValue::Exception(e) => Err(e),
+            JsValue::BigNum(_) => Err(self
+                .ctx
+                .throw_type_error("BigInt value can't be serialized in JSON")),
+            JsValue::Array(_)
+            | JsValue::Object(_)
+            | JsValue::Promise(_)
+            | JsValue::ArrayBuffer(_)
+            | JsValue::TypedArray(_)
+            | JsValue::DataView(_)
+            | JsValue::Date(_)
+            | JsValue::RegExp(_)
+            | JsValue::Map(_)
+            | JsValue::Set(_) => self.walk_object(value).map(Some),
+            JsValue::Module(_) | JsValue::FunctionByteCode(_) | JsValue::Other(_) => {
+                let msg = format!("{} can't be converted to JSON", value.type_name());
+                Err(self.ctx.throw_type_error(&msg))
+            }
+        }
+    }
+
+    // what `value.toJSON(key)` gives, when it has such a method
+    fn call_to_json(&mut self, key: &str, value: JsValue) -> Result<JsValue, JsException> {
+        match value {
+            JsValue::Array(_)
+            | JsValue::Object(_)
+            | JsValue::Promise(_)
+            | JsValue::ArrayBuffer(_)
+            | JsValue::TypedArray(_)
+            | JsValue::DataView(_)
+            | JsValue::Date(_)
+            | JsValue::RegExp(_)
+            | JsValue::Map(_)
+            | JsValue::Set(_)
+            | JsValue::BigNum(_) => {}
+            _ => return Ok(value),
+        }
+        let ctx = self.ctx.ctx;
+        let to_json = unsafe {
+            let f = JS_GetPropertyStr(ctx, value.get_qjs_value(), "toJSON\0".as_ptr().cast());
+            JsValue::from_qjs_value(ctx, f)
+        };
+        match to_json {
+            JsValue::Function(f) => {
+                let key = self.ctx.new_string(key).into();
+                match f.call_with_this(&value, &[key]) {
+                    JsValue::Exception(e) => Err(e),
+                    v => Ok(v),
+                }
+            }
+            JsValue::Exception(e) => Err(e),
+            _ => Ok(value),
+        }
+    }
+
+    fn walk_object(&mut self, value: JsValue) -> Result<Value, JsException> {
+        let v = value.get_qjs_value();
+        if let JsValue::Object(_) = value {
+            // `new Number(1)` and friends stand for what they wrap
+            if unsafe { JS_GetCloneKind_real(v) as u32 } == JS_CLONE_KIND_PRIMITIVE_WRAPPER {
+                let ctx = self.ctx.ctx;
+                let inner = unsafe {
+                    JsValue::from_qjs_value(ctx, JS_GetPrimitiveWrapperValue_real(ctx, v))
+                };
+                return Ok(self.walk("", inner)?.unwrap_or(Value::Null));
+            }
+        }
+
+        let id = unsafe { JS_VALUE_GET_PTR_real(v) as usize };
+        if self.stack.contains(&id) {
+            return Err(self
+                .ctx
+                .throw_type_error("circular reference in JSON value"));
+        }
+        if self.stack.len() == MAX_DEPTH {
+            return Err(self
+                .ctx
+                .throw_range_error("Maximum call stack size exceeded"));
+        }
+        self.stack.push(id);
+        let r = match &value {
+            JsValue::Array(array) => array.to_vec().and_then(|items| self.walk_array(items)),
+            _ => self.walk_entries(v),
+        };
+        self.stack.pop();
+        r
+    }
+
+    fn walk_array(&mut self, items: Vec<JsValue>) -> Result<Value, JsException> {
+        let mut array = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let item = self.walk(&i.to_string(), item)?;
+            array.push(item.unwrap_or(Value::Null));
+        }
+        Ok(Value::Array(array))
+    }
+
+    // the own enumerable string-keyed properties, like `Object.keys`
+    fn walk_entries(&mut self, obj: JSValue) -> Result<Value, JsException> {
+        let ctx = self.ctx.ctx;
+        let mut entries = Vec::new();
+        unsafe {
+            let mut properties: *mut JSPropertyEnum = std::ptr::null_mut();
+            let mut count: u32 = 0;
+
+            let flags = (JS_GPN_STRING_MASK | JS_GPN_ENUM_ONLY) as i32;
+            let ret = JS_GetOwnPropertyNames(ctx, &mut properties, &mut count, obj, flags);
+            if ret != 0 {
+                return Err(JsException(JsRef {
+                    ctx,
+                    v: js_exception(),
+                }));
+            }
+
+            let properties = DroppableValue::new(properties, |&mut properties| {
+                for index in 0..count {
+                    let prop = properties.offset(index as isize);
+                    JS_FreeAtom(ctx, (*prop).atom);
+                }
+                js_free(ctx, properties as *mut std::ffi::c_void);
+            });
+
+            for index in 0..count {
+                let prop = (*properties).offset(index as isize);
+                let key = JsValue::from_qjs_value(ctx, JS_AtomToString(ctx, (*prop).atom));
+                let key = match key {
+                    JsValue::String(key) => key.to_string(),
+                    JsValue::Exception(e) => return Err(e),
+                    _ => continue,
+                };
+                let raw_value = JS_GetPropertyInternal(ctx, obj, (*prop).atom, obj, 0);
+                entries.push((key, JsValue::from_qjs_value(ctx, raw_value)));
+            }
+        }
+
+        let mut map = Map::new();
+        for (key, value) in entries {
+            if let Some(value) = self.walk(&key, value)? {
+                map.insert(key, value);
+            }
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+// `JSON.stringify` writes `null` for NaN and the infinities, and integral
+// numbers without a fraction
+#[cfg(feature = "serde_json")]
+fn float_to_json(n: f64) -> Value {
+    const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+        Value::from(n as i64)
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn number_to_js(n: &Number) -> JsValue {
+    match n.as_i64().map(i32::try_from) {
+        Some(Ok(n)) => JsValue::Int(n),
+        _ => JsValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl IntoJsValue for Value {
+    fn into_js_value(self, ctx: &mut Context) -> JsValue {
+        ctx.json_value_to_js(&self)
+    }
+}
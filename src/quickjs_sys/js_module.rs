@@ -1,6 +1,8 @@
 use super::qjs as q;
 use crate::quickjs_sys::qjs::{JSContext, JSModuleDef};
 use crate::{Context, JsValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::ops::DerefMut;
 
@@ -61,6 +63,53 @@ impl<F: Fn(&mut Context, &mut JsModuleDef)> FnModuleInitFnTrampoline<F> {
     }
 }
 
+// Closures passed to `register_closure_module`, keyed by the module they
+// initialize. QuickJS gives module init functions no data pointer.
+thread_local! {
+    static MODULE_INITS: RefCell<HashMap<usize, Box<dyn FnOnce(&mut Context, &mut JsModuleDef)>>> =
+        RefCell::new(HashMap::new());
+}
+
+unsafe extern "C" fn closure_module_init(
+    ctx: *mut JSContext,
+    m: *mut JSModuleDef,
+) -> ::std::os::raw::c_int {
+    let init_module = MODULE_INITS.with(|inits| inits.borrow_mut().remove(&(m as usize)));
+    if let Some(init_module) = init_module {
+        let mut m = JsModuleDef { ctx, m };
+        let mut n_ctx = std::mem::ManuallyDrop::new(Context { ctx });
+        init_module(n_ctx.deref_mut(), &mut m);
+    }
+    0
+}
+
+unsafe fn new_c_module<S: ToString>(
+    ctx: &mut Context,
+    name: S,
+    exports: &[&str],
+    init: q::JSModuleInitFunc,
+) -> *mut JSModuleDef {
+    let mut name = name.to_string();
+    if !name.ends_with('\0') {
+        name.push('\0');
+    }
+
+    let ctx = ctx.ctx;
+    let m = q::JS_NewCModule(ctx, name.as_ptr().cast(), init);
+
+    let mut export_string = String::new();
+
+    for s in exports {
+        export_string.clear();
+        export_string.push_str(*s);
+        if !export_string.ends_with('\0') {
+            export_string.push('\0');
+        }
+        q::JS_AddModuleExport(ctx, m, export_string.as_ptr().cast());
+    }
+    m
+}
+
 fn register_fn_module<F: Fn(&mut Context, &mut JsModuleDef), S: ToString>(
     ctx: &mut Context,
     name: S,
@@ -68,49 +117,15 @@ fn register_fn_module<F: Fn(&mut Context, &mut JsModuleDef), S: ToString>(
     _f: F,
 ) {
     unsafe {
-        let mut name = name.to_string();
-        if !name.ends_with('\0') {
-            name.push('\0');
-        }
-
-        let ctx = ctx.ctx;
         let js_module_init = FnModuleInitFnTrampoline::<F>::init_module;
-        let m = q::JS_NewCModule(ctx, name.as_ptr().cast(), Some(js_module_init));
-
-        let mut export_string = String::new();
-
-        for s in exports {
-            export_string.clear();
-            export_string.push_str(*s);
-            if !export_string.ends_with('\0') {
-                export_string.push('\0');
-            }
-            q::JS_AddModuleExport(ctx, m, export_string.as_ptr().cast());
-        }
+        new_c_module(ctx, name, exports, Some(js_module_init));
     }
 }
 
 fn register_module<F: ModuleInit, S: ToString>(ctx: &mut Context, name: S, exports: &[&str]) {
     unsafe {
-        let mut name = name.to_string();
-        if !name.ends_with('\0') {
-            name.push('\0');
-        }
-
-        let ctx = ctx.ctx;
         let js_module_init = ModuleInitFnTrampoline::<F>::init_module;
-        let m = q::JS_NewCModule(ctx, name.as_ptr().cast(), Some(js_module_init));
-
-        let mut export_string = String::new();
-
-        for s in exports {
-            export_string.clear();
-            export_string.push_str(*s);
-            if !export_string.ends_with('\0') {
-                export_string.push('\0');
-            }
-            q::JS_AddModuleExport(ctx, m, export_string.as_ptr().cast());
-        }
+        new_c_module(ctx, name, exports, Some(js_module_init));
     }
 }
 
@@ -128,4 +143,20 @@ impl Context {
         assert_size_zero!(@module, F);
         register_fn_module::<F, S>(self, name, exports, f)
     }
+
+    /// Like `register_fn_module`, but `f` may capture state, such as closures
+    /// made with `new_closure`. It runs once, when the module is first
+    /// imported.
+    pub fn register_closure_module<F, S>(&mut self, name: S, exports: &[&str], f: F)
+    where
+        F: FnOnce(&mut Context, &mut JsModuleDef) + 'static,
+        S: ToString,
+    {
+        unsafe {
+            let m = new_c_module(self, name, exports, Some(closure_module_init));
+            if !m.is_null() {
+                MODULE_INITS.with(|inits| inits.borrow_mut().insert(m as usize, Box::new(f)));
+            }
+        }
+    }
 }
@@ -0,0 +1,160 @@
+use super::qjs::*;
+use super::{make_c_string, AsObject, Context, JsFunction, JsObject, JsRef, JsSymbol, JsValue};
+
+/// The key of a property: a string, a symbol or an array index.
+#[derive(Debug, Clone, Copy)]
+pub enum PropertyKey<'a> {
+    String(&'a str),
+    Symbol(&'a JsSymbol),
+    Index(u32),
+}
+
+impl<'a> PropertyKey<'a> {
+    /// A new atom for the key, which the caller frees with `JS_FreeAtom`.
+    pub(super) fn to_atom(self, ctx: *mut JSContext) -> JSAtom {
+        unsafe {
+            match self {
+                PropertyKey::String(s) => JS_NewAtom(ctx, make_c_string(s).as_ptr()),
+                PropertyKey::Symbol(s) => s.to_atom(),
+                PropertyKey::Index(i) => JS_NewAtomUInt32(ctx, i),
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a str> for PropertyKey<'a> {
+    fn from(s: &'a str) -> Self {
+        PropertyKey::String(s)
+    }
+}
+
+impl<'a> From<&'a String> for PropertyKey<'a> {
+    fn from(s: &'a String) -> Self {
+        PropertyKey::String(s)
+    }
+}
+
+impl<'a> From<&'a JsSymbol> for PropertyKey<'a> {
+    fn from(s: &'a JsSymbol) -> Self {
+        PropertyKey::Symbol(s)
+    }
+}
+
+impl<'a> From<u32> for PropertyKey<'a> {
+    fn from(i: u32) -> Self {
+        PropertyKey::Index(i)
+    }
+}
+
+/// The attributes of a property. `writable` has no meaning for accessors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyFlags {
+    pub writable: bool,
+    pub enumerable: bool,
+    pub configurable: bool,
+}
+
+impl PropertyFlags {
+    /// What an assignment creates.
+    pub const DEFAULT: Self = PropertyFlags {
+        writable: true,
+        enumerable: true,
+        configurable: true,
+    };
+
+    /// Left out of `for...in` and `Object.keys`, like the methods of the
+    /// builtins.
+    pub const HIDDEN: Self = PropertyFlags {
+        writable: true,
+        enumerable: false,
+        configurable: true,
+    };
+
+    /// Enumerable, but can neither be assigned nor deleted.
+    pub const READ_ONLY: Self = PropertyFlags {
+        writable: false,
+        enumerable: true,
+        configurable: false,
+    };
+
+    /// Hidden, and can neither be assigned nor deleted.
+    pub const CONSTANT: Self = PropertyFlags {
+        writable: false,
+        enumerable: false,
+        configurable: false,
+    };
+
+    pub(super) fn from_bits(bits: u32) -> Self {
+        PropertyFlags {
+            writable: bits & JS_PROP_WRITABLE != 0,
+            enumerable: bits & JS_PROP_ENUMERABLE != 0,
+            configurable: bits & JS_PROP_CONFIGURABLE != 0,
+        }
+    }
+
+    // `JS_DefineProperty` flags that set every attribute, not only the ones
+    // that are true
+    pub(super) fn define_bits(self, accessor: bool) -> i32 {
+        let mut bits = JS_PROP_THROW | JS_PROP_HAS_ENUMERABLE | JS_PROP_HAS_CONFIGURABLE;
+        if !accessor {
+            bits |= JS_PROP_HAS_WRITABLE;
+            if self.writable {
+                bits |= JS_PROP_WRITABLE;
+            }
+        }
+        if self.enumerable {
+            bits |= JS_PROP_ENUMERABLE;
+        }
+        if self.configurable {
+            bits |= JS_PROP_CONFIGURABLE;
+        }
+        bits as i32
+    }
+}
+
+impl Default for PropertyFlags {
+    fn default() -> Self {
+        PropertyFlags::DEFAULT
+    }
+}
+
+/// An own property as `Object.getOwnPropertyDescriptor` describes it.
+/// `value` is `undefined` for an accessor, which has a `getter`, a `setter`
+/// or both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyDescriptor {
+    pub value: JsValue,
+    pub getter: Option<JsFunction>,
+    pub setter: Option<JsFunction>,
+    pub flags: PropertyFlags,
+}
+
+impl PropertyDescriptor {
+    pub fn is_accessor(&self) -> bool {
+        self.getter.is_some() || self.setter.is_some()
+    }
+
+    pub(super) fn from_qjs(ctx: *mut JSContext, desc: JSPropertyDescriptor) -> Self {
+        let function = |v| match JsValue::from_qjs_value(ctx, v) {
+            JsValue::Function(f) => Some(f),
+            _ => None,
+        };
+        PropertyDescriptor {
+            value: JsValue::from_qjs_value(ctx, desc.value),
+            getter: function(desc.getter),
+            setter: function(desc.setter),
+            flags: PropertyFlags::from_bits(desc.flags as u32),
+        }
+    }
+}
+
+// `Object[method](obj)`, for what has no C API like `freeze`
+pub(super) fn call_object_method(r: &JsRef, method: &str) -> JsValue {
+    let mut ctx = std::mem::ManuallyDrop::new(Context { ctx: r.ctx });
+    match ctx.get_global().get("Object") {
+        JsValue::Function(mut object) => {
+            object.invoke(method, &[JsValue::Object(JsObject(r.clone()))])
+        }
+        v => v,
+    }
+}
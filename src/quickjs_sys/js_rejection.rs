@@ -0,0 +1,117 @@
+use super::qjs::*;
+use super::{AsObject, Context, JsError, JsValue};
+use crate::event_loop::UnhandledRejections;
+
+pub(super) unsafe extern "C" fn promise_rejection_tracker(
+    ctx: *mut JSContext,
+    promise: JSValue,
+    reason: JSValue,
+    is_handled: ::std::os::raw::c_int,
+    _opaque: *mut ::std::os::raw::c_void,
+) {
+    let rt = JS_GetRuntime(ctx);
+    if let Some(event_loop) = (JS_GetRuntimeOpaque(rt) as *mut crate::EventLoop).as_mut() {
+        let promise = JsValue::from_qjs_value(ctx, JS_DupValue_real(ctx, promise));
+        let reason = JsValue::from_qjs_value(ctx, JS_DupValue_real(ctx, reason));
+        event_loop
+            .rejections
+            .track(promise, reason, is_handled != 0);
+    }
+}
+
+/// The error an unhandled rejection is raised as. Like in Node, a reason that
+/// is not an object gets a message saying where it came from.
+fn unhandled_rejection_error(ctx: &mut Context, reason: JsValue) -> JsError {
+    let mut e = JsError::from_value(ctx, reason);
+    if !matches!(e.value, JsValue::Object(_)) {
+        e.message = format!(
+            "This error originated either by throwing inside of an async function \
+             without a catch block, or by rejecting a promise which was not handled \
+             with .catch(). The promise rejected with the reason \"{}\".",
+            e.message
+        );
+        e.name = "UnhandledPromiseRejection".to_string();
+    }
+    e
+}
+
+/// At most this many reported promises are remembered for
+/// `rejectionHandled`, the oldest are forgotten first.
+const MAX_REPORTED: usize = 10_000;
+
+/// Drops the reported promises nothing but the list refers to anymore, as
+/// no handler can be added to them, and caps what is left at `MAX_REPORTED`
+/// for promises kept alive by a cycle.
+fn forget_unreachable(reported: &mut Vec<JsValue>) {
+    reported.retain(|promise| unsafe { JS_GetRefCount_real(promise.get_qjs_value()) > 1 });
+    if reported.len() > MAX_REPORTED {
+        reported.drain(..reported.len() - MAX_REPORTED);
+    }
+}
+
+fn emit(ctx: &mut Context, event: &str, args: &[JsValue]) -> Result<bool, JsError> {
+    let mut process = match ctx.get_global().get("process") {
+        JsValue::Object(process) => process,
+        _ => return Ok(false),
+    };
+    let mut argv = vec![ctx.new_string(event).into()];
+    argv.extend_from_slice(args);
+    match process.invoke("emit", &argv) {
+        JsValue::Exception(_) => Err(ctx.take_exception()),
+        JsValue::Bool(listened) => Ok(listened),
+        _ => Ok(false),
+    }
+}
+
+impl Context {
+    /// Emits `rejectionHandled` and `unhandledRejection` for the promises the
+    /// rejection tracker saw since the last call, according to the
+    /// `UnhandledRejections` mode. Returns whether any listener ran, so that
+    /// the jobs they queued get drained first, or the rejection raised as an
+    /// uncaught exception.
+    pub(crate) fn report_rejections(&mut self) -> Result<bool, JsError> {
+        let (mode, pending, handled) = match self.event_loop() {
+            Some(event_loop) => {
+                let rejections = &mut event_loop.rejections;
+                (
+                    rejections.mode,
+                    std::mem::take(&mut rejections.pending),
+                    std::mem::take(&mut rejections.handled),
+                )
+            }
+            None => return Ok(false),
+        };
+        let emitted = !pending.is_empty() || !handled.is_empty();
+
+        for promise in handled {
+            emit(self, "rejectionHandled", &[promise])?;
+        }
+        for (promise, reason) in pending {
+            if mode == UnhandledRejections::Strict {
+                return Err(unhandled_rejection_error(self, reason));
+            }
+            let listened = emit(
+                self,
+                "unhandledRejection",
+                &[reason.clone(), promise.clone()],
+            )?;
+            match mode {
+                UnhandledRejections::Throw if !listened => {
+                    return Err(unhandled_rejection_error(self, reason));
+                }
+                UnhandledRejections::Warn => {
+                    let e = unhandled_rejection_error(self, reason);
+                    eprintln!("UnhandledPromiseRejectionWarning: {:#}", e);
+                }
+                _ => {}
+            }
+            if let Some(event_loop) = self.event_loop() {
+                event_loop.rejections.reported.push(promise);
+            }
+        }
+        if let Some(event_loop) = self.event_loop() {
+            forget_unreachable(&mut event_loop.rejections.reported);
+        }
+        Ok(emitted)
+    }
+}
@@ -0,0 +1,933 @@
+//! A serde `Serializer` and `Deserializer` over `JsValue`, behind the `serde`
+//! feature. Objects map to structs and maps, arrays to sequences and
+//! `ArrayBuffer`s and typed arrays to bytes. A `Map` or `Set` can be read
+//! like an object or an array. `None` and `()` become `null`;
+//! both `null` and `undefined` deserialize as `None`.
+//!
+//! Enums use serde's externally tagged representation, as serde_json does:
+//! unit variants are strings, other variants `{ "Variant": value }`.
+
+use super::{AsObject, BigNumKind, Context, ConvertError, JsArray, JsBigNum, JsObject, JsValue};
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser::{self, Serialize};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerdeError(pub String);
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+impl From<ConvertError> for SerdeError {
+    fn from(e: ConvertError) -> Self {
+        SerdeError(e.to_string())
+    }
+}
+
+impl Context {
+    /// Serializes `value` into a new JS value.
+    pub fn to_js<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<JsValue, SerdeError> {
+        value.serialize(&mut Serializer { ctx: self })
+    }
+
+    /// Deserializes a `T` out of `value`.
+    pub fn from_js<T: DeserializeOwned>(&mut self, value: &JsValue) -> Result<T, SerdeError> {
+        T::deserialize(Deserializer {
+            ctx: self,
+            value: value.clone(),
+        })
+    }
+}
+
+pub struct Serializer<'a> {
+    ctx: &'a mut Context,
+}
+
+impl<'a> Serializer<'a> {
+    pub fn new(ctx: &'a mut Context) -> Self {
+        Serializer { ctx }
+    }
+
+    // `{ variant: value }`, for every enum variant that carries data
+    fn tagged(&mut self, variant: &str, value: JsValue) -> JsValue {
+        let mut obj = self.ctx.new_object();
+        obj.set(variant, value);
+        obj.into()
+    }
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    type SerializeSeq = SerializeArray<'a, 'b>;
+    type SerializeTuple = SerializeArray<'a, 'b>;
+    type SerializeTupleStruct = SerializeArray<'a, 'b>;
+    type SerializeTupleVariant = SerializeArray<'a, 'b>;
+    type SerializeMap = SerializeObject<'a, 'b>;
+    type SerializeStruct = SerializeObject<'a, 'b>;
+    type SerializeStructVariant = SerializeObject<'a, 'b>;
+
+    fn serialize_bool(self, v: bool) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Int(v as i32))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Int(v as i32))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<JsValue, SerdeError> {
+        Ok(match i32::try_from(v) {
+            Ok(n) => JsValue::Int(n),
+            Err(_) => JsValue::Float(v as f64),
+        })
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Int(v as i32))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Int(v as i32))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<JsValue, SerdeError> {
+        Ok(match i32::try_from(v) {
+            Ok(n) => JsValue::Int(n),
+            Err(_) => JsValue::Float(v as f64),
+        })
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<JsValue, SerdeError> {
+        Ok(match i32::try_from(v) {
+            Ok(n) => JsValue::Int(n),
+            Err(_) => JsValue::Float(v as f64),
+        })
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Float(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<JsValue, SerdeError> {
+        Ok(self.ctx.new_string(v.encode_utf8(&mut [0; 4])).into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<JsValue, SerdeError> {
+        Ok(self.ctx.new_string(v).into())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<JsValue, SerdeError> {
+        Ok(self.ctx.new_array_buffer(v).into())
+    }
+
+    fn serialize_none(self) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<JsValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<JsValue, SerdeError> {
+        Ok(JsValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<JsValue, SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<JsValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<JsValue, SerdeError> {
+        let value = value.serialize(&mut *self)?;
+        Ok(self.tagged(variant, value))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        let array = self.ctx.new_array();
+        Ok(SerializeArray {
+            ser: self,
+            array,
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        let array = self.ctx.new_array();
+        Ok(SerializeArray {
+            ser: self,
+            array,
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        let obj = self.ctx.new_object();
+        Ok(SerializeObject {
+            ser: self,
+            obj,
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        let obj = self.ctx.new_object();
+        Ok(SerializeObject {
+            ser: self,
+            obj,
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+pub struct SerializeArray<'a, 'b> {
+    ser: &'b mut Serializer<'a>,
+    array: JsArray,
+    variant: Option<&'static str>,
+}
+
+impl<'a, 'b> SerializeArray<'a, 'b> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let value = value.serialize(&mut *self.ser)?;
+        let len = self.array.get_length();
+        self.array.put(len, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<JsValue, SerdeError> {
+        match self.variant {
+            Some(variant) => Ok(self.ser.tagged(variant, self.array.into())),
+            None => Ok(self.array.into()),
+        }
+    }
+}
+
+impl<'a, 'b> ser::SerializeSeq for SerializeArray<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, 'b> ser::SerializeTuple for SerializeArray<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, 'b> ser::SerializeTupleStruct for SerializeArray<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, 'b> ser::SerializeTupleVariant for SerializeArray<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+pub struct SerializeObject<'a, 'b> {
+    ser: &'b mut Serializer<'a>,
+    obj: JsObject,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl<'a, 'b> SerializeObject<'a, 'b> {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerdeError> {
+        let value = value.serialize(&mut *self.ser)?;
+        self.obj.set(key, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<JsValue, SerdeError> {
+        match self.variant {
+            Some(variant) => Ok(self.ser.tagged(variant, self.obj.into())),
+            None => Ok(self.obj.into()),
+        }
+    }
+}
+
+impl<'a, 'b> ser::SerializeMap for SerializeObject<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError("serialize_value called before serialize_key".into()))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, 'b> ser::SerializeStruct for SerializeObject<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, 'b> ser::SerializeStructVariant for SerializeObject<'a, 'b> {
+    type Ok = JsValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<JsValue, SerdeError> {
+        self.finish()
+    }
+}
+
+/// Property keys are strings, so map keys have to be strings, chars, numbers
+/// or unit variants.
+struct MapKeySerializer;
+
+fn key_must_be_a_string() -> SerdeError {
+    SerdeError("object key must be a string or a number".into())
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = SerdeError;
+
+    type SerializeSeq = ser::Impossible<String, SerdeError>;
+    type SerializeTuple = ser::Impossible<String, SerdeError>;
+    type SerializeTupleStruct = ser::Impossible<String, SerdeError>;
+    type SerializeTupleVariant = ser::Impossible<String, SerdeError>;
+    type SerializeMap = ser::Impossible<String, SerdeError>;
+    type SerializeStruct = ser::Impossible<String, SerdeError>;
+    type SerializeStructVariant = ser::Impossible<String, SerdeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerdeError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+}
+
+pub struct Deserializer<'a> {
+    ctx: &'a mut Context,
+    value: JsValue,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(ctx: &'a mut Context, value: JsValue) -> Self {
+        Deserializer { ctx, value }
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        self.value.as_bytes().map(<[u8]>::to_vec)
+    }
+
+    fn unsupported(&self) -> SerdeError {
+        SerdeError(format!("cannot deserialize a {}", self.value.type_name()))
+    }
+}
+
+// the smallest integer type that holds `n`, or a float for a BigFloat or
+// BigDecimal that isn't an integer
+fn visit_bignum<'de, V: Visitor<'de>>(n: &JsBigNum, visitor: V) -> Result<V::Value, SerdeError> {
+    if let Some(n) = n.to_i64() {
+        visitor.visit_i64(n)
+    } else if let Some(n) = n.to_u64() {
+        visitor.visit_u64(n)
+    } else if let Some(n) = n.to_i128() {
+        visitor.visit_i128(n)
+    } else if let Some(n) = n.to_u128() {
+        visitor.visit_u128(n)
+    } else if n.kind() == BigNumKind::BigInt {
+        Err(SerdeError(format!(
+            "{} does not fit in 128 bits",
+            n.to_decimal_string()
+        )))
+    } else {
+        visitor.visit_f64(n.to_f64())
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Deserializer<'a> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match &self.value {
+            JsValue::Int(n) => visitor.visit_i32(*n),
+            // integral floats go through `visit_i64`/`visit_u64`, which the
+            // integer visitors accept and `visit_f64` is not
+            JsValue::Float(f) if f.fract() == 0.0 && f.abs() < 9007199254740992.0 => {
+                if *f < 0.0 {
+                    visitor.visit_i64(*f as i64)
+                } else {
+                    visitor.visit_u64(*f as u64)
+                }
+            }
+            JsValue::Float(f) => visitor.visit_f64(*f),
+            JsValue::BigNum(n) => visit_bignum(n, visitor),
+            JsValue::String(s) => visitor.visit_string(s.to_string()),
+            JsValue::Bool(b) => visitor.visit_bool(*b),
+            JsValue::Null | JsValue::UnDefined => visitor.visit_unit(),
+            JsValue::Array(array) => {
+                let items = array
+                    .to_vec()
+                    .map_err(|_| SerdeError("failed to read array".into()))?;
+                visitor.visit_seq(JsSeqAccess {
+                    ctx: self.ctx,
+                    items: items.into_iter(),
+                })
+            }
+            JsValue::Set(set) => {
+                let items = set
+                    .values()
+                    .map_err(|_| SerdeError("failed to read Set".into()))?;
+                visitor.visit_seq(JsSeqAccess {
+                    ctx: self.ctx,
+                    items: items.into_iter(),
+                })
+            }
+            // like `JSON.stringify`, which gives `null` for an invalid date
+            JsValue::Date(date) => match date.to_iso_string() {
+                Some(s) => visitor.visit_string(s),
+                None => visitor.visit_unit(),
+            },
+            JsValue::ArrayBuffer(_) | JsValue::TypedArray(_) | JsValue::DataView(_) => {
+                match self.bytes() {
+                    Some(bytes) => visitor.visit_byte_buf(bytes),
+                    None => Err(self.unsupported()),
+                }
+            }
+            JsValue::Object(obj) => {
+                let properties = obj
+                    .to_map()
+                    .map_err(|_| SerdeError("failed to read object".into()))?;
+                let entries: Vec<_> = properties
+                    .into_iter()
+                    .map(|(key, value)| (self.ctx.new_string(&key).into(), value))
+                    .collect();
+                visitor.visit_map(JsMapAccess {
+                    ctx: self.ctx,
+                    entries: entries.into_iter(),
+                    value: None,
+                })
+            }
+            // keys are deserialized like values, so a `Map` with number keys
+            // can become a `HashMap<u32, _>`
+            JsValue::Map(map) => {
+                let entries = map
+                    .entries()
+                    .map_err(|_| SerdeError("failed to read Map".into()))?;
+                visitor.visit_map(JsMapAccess {
+                    ctx: self.ctx,
+                    entries: entries.into_iter(),
+                    value: None,
+                })
+            }
+            _ => Err(self.unsupported()),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            JsValue::Null | JsValue::UnDefined => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.bytes() {
+            Some(bytes) => visitor.visit_byte_buf(bytes),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    // lets a `Vec<u8>` be read from a buffer as well as from an array
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.bytes() {
+            Some(bytes) => visitor.visit_seq(de::value::SeqDeserializer::new(bytes.into_iter())),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let (variant, value) = match &self.value {
+            JsValue::String(s) => (s.to_string(), None),
+            JsValue::Object(obj) => {
+                let properties = obj
+                    .to_map()
+                    .map_err(|_| SerdeError("failed to read object".into()))?;
+                if properties.len() != 1 {
+                    return Err(SerdeError(
+                        "expected an object with a single key for an enum".into(),
+                    ));
+                }
+                let (variant, value) = properties.into_iter().next().unwrap();
+                (variant, Some(value))
+            }
+            _ => {
+                return Err(SerdeError(format!(
+                    "expected a string or an object for an enum, got {}",
+                    self.value.type_name()
+                )))
+            }
+        };
+        visitor.visit_enum(JsEnumAccess {
+            ctx: self.ctx,
+            variant,
+            value,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct JsSeqAccess<'a> {
+    ctx: &'a mut Context,
+    items: std::vec::IntoIter<JsValue>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for JsSeqAccess<'a> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        match self.items.next() {
+            Some(value) => seed
+                .deserialize(Deserializer {
+                    ctx: &mut *self.ctx,
+                    value,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct JsMapAccess<'a> {
+    ctx: &'a mut Context,
+    entries: std::vec::IntoIter<(JsValue, JsValue)>,
+    value: Option<JsValue>,
+}
+
+impl<'de, 'a> MapAccess<'de> for JsMapAccess<'a> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Deserializer {
+                    ctx: &mut *self.ctx,
+                    value: key,
+                })
+                .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SerdeError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| SerdeError("next_value_seed called before next_key_seed".into()))?;
+        seed.deserialize(Deserializer {
+            ctx: &mut *self.ctx,
+            value,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct JsEnumAccess<'a> {
+    ctx: &'a mut Context,
+    variant: String,
+    value: Option<JsValue>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for JsEnumAccess<'a> {
+    type Error = SerdeError;
+    type Variant = JsVariantAccess<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, JsVariantAccess<'a>), SerdeError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((
+            variant,
+            JsVariantAccess {
+                ctx: self.ctx,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct JsVariantAccess<'a> {
+    ctx: &'a mut Context,
+    value: Option<JsValue>,
+}
+
+impl<'a> JsVariantAccess<'a> {
+    fn into_deserializer(self) -> Result<Deserializer<'a>, SerdeError> {
+        match self.value {
+            Some(value) => Ok(Deserializer {
+                ctx: self.ctx,
+                value,
+            }),
+            None => Err(SerdeError("expected an enum variant with a value".into())),
+        }
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for JsVariantAccess<'a> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.value {
+            None | Some(JsValue::Null) | Some(JsValue::UnDefined) => Ok(()),
+            Some(_) => Err(SerdeError("expected a unit enum variant".into())),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self.into_deserializer()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self.into_deserializer()?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self.into_deserializer()?, visitor)
+    }
+}
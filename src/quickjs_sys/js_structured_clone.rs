@@ -0,0 +1,1112 @@
+use super::qjs::*;
+use super::{
+    AsObject, BigNumKind, Context, JsArray, JsArrayBuffer, JsException, JsMap, JsObject, JsRef,
+    JsRegExp, JsSet, JsString, JsValue, PropertyFlags, TypedArrayKind,
+};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+// The wire format of V8's `ValueSerializer`, which `v8.serialize` in Node
+// writes too, so plain data can be passed between the two.
+const VERSION: u64 = 15;
+// the first version whose array buffer views end with flags
+const VERSION_VIEW_FLAGS: u64 = 14;
+// how deep values may nest, so a long chain of objects throws instead of
+// overflowing the stack
+const MAX_DEPTH: usize = 2048;
+
+mod tag {
+    pub const VERSION: u8 = 0xFF;
+    pub const PADDING: u8 = b'\0';
+    pub const VERIFY_OBJECT_COUNT: u8 = b'?';
+    pub const UNDEFINED: u8 = b'_';
+    pub const NULL: u8 = b'0';
+    pub const TRUE: u8 = b'T';
+    pub const FALSE: u8 = b'F';
+    pub const INT32: u8 = b'I';
+    pub const UINT32: u8 = b'U';
+    pub const DOUBLE: u8 = b'N';
+    pub const BIGINT: u8 = b'Z';
+    pub const UTF8_STRING: u8 = b'S';
+    pub const ONE_BYTE_STRING: u8 = b'"';
+    pub const TWO_BYTE_STRING: u8 = b'c';
+    pub const OBJECT_REFERENCE: u8 = b'^';
+    pub const BEGIN_OBJECT: u8 = b'o';
+    pub const END_OBJECT: u8 = b'{';
+    pub const BEGIN_SPARSE_ARRAY: u8 = b'a';
+    pub const END_SPARSE_ARRAY: u8 = b'@';
+    pub const BEGIN_DENSE_ARRAY: u8 = b'A';
+    pub const END_DENSE_ARRAY: u8 = b'$';
+    pub const THE_HOLE: u8 = b'-';
+    pub const DATE: u8 = b'D';
+    pub const TRUE_OBJECT: u8 = b'y';
+    pub const FALSE_OBJECT: u8 = b'x';
+    pub const NUMBER_OBJECT: u8 = b'n';
+    pub const BIGINT_OBJECT: u8 = b'z';
+    pub const STRING_OBJECT: u8 = b's';
+    pub const REGEXP: u8 = b'R';
+    pub const BEGIN_MAP: u8 = b';';
+    pub const END_MAP: u8 = b':';
+    pub const BEGIN_SET: u8 = b'\'';
+    pub const END_SET: u8 = b',';
+    pub const ARRAY_BUFFER: u8 = b'B';
+    pub const ARRAY_BUFFER_TRANSFER: u8 = b't';
+    pub const ARRAY_BUFFER_VIEW: u8 = b'V';
+    pub const ERROR: u8 = b'r';
+}
+
+// what follows `tag::ERROR`, up to `END`
+mod error_tag {
+    pub const MESSAGE: u8 = b'm';
+    pub const CAUSE: u8 = b'c';
+    pub const STACK: u8 = b's';
+    pub const END: u8 = b'.';
+}
+
+const ERROR_PROTOTYPES: &[(u8, &str)] = &[
+    (b'E', "EvalError"),
+    (b'R', "RangeError"),
+    (b'F', "ReferenceError"),
+    (b'S', "SyntaxError"),
+    (b'T', "TypeError"),
+    (b'U', "URIError"),
+];
+
+const REGEXP_FLAGS: &[(char, u64)] = &[
+    ('g', 1 << 0),
+    ('i', 1 << 1),
+    ('m', 1 << 2),
+    ('y', 1 << 3),
+    ('u', 1 << 4),
+    ('s', 1 << 5),
+    ('d', 1 << 7),
+];
+
+// `None` for a `DataView`
+const VIEW_SUBTAGS: &[(u8, Option<TypedArrayKind>)] = &[
+    (b'b', Some(TypedArrayKind::Int8)),
+    (b'B', Some(TypedArrayKind::Uint8)),
+    (b'C', Some(TypedArrayKind::Uint8Clamped)),
+    (b'w', Some(TypedArrayKind::Int16)),
+    (b'W', Some(TypedArrayKind::Uint16)),
+    (b'd', Some(TypedArrayKind::Int32)),
+    (b'D', Some(TypedArrayKind::Uint32)),
+    (b'f', Some(TypedArrayKind::Float32)),
+    (b'F', Some(TypedArrayKind::Float64)),
+    (b'q', Some(TypedArrayKind::BigInt64)),
+    (b'Q', Some(TypedArrayKind::BigUint64)),
+    (b'?', None),
+];
+
+// objects are told apart by identity, which is where they live
+fn identity(v: JSValue) -> usize {
+    unsafe { JS_VALUE_GET_PTR_real(v) as usize }
+}
+
+// the bytes of a buffer, `None` once it is detached
+fn array_buffer_bytes(buffer: &JsArrayBuffer) -> Option<&[u8]> {
+    unsafe {
+        let r = &buffer.0;
+        let mut len = 0;
+        let ptr = JS_GetArrayBuffer(r.ctx, &mut len, r.v);
+        if ptr.is_null() {
+            // the TypeError a detached buffer throws
+            JS_FreeValue_real(r.ctx, JS_GetException(r.ctx));
+            return None;
+        }
+        Some(std::slice::from_raw_parts(ptr, len))
+    }
+}
+
+// the own enumerable string-keyed properties of `obj`, in property order
+fn own_entries(ctx: *mut JSContext, obj: JSValue) -> Result<Vec<(String, JsValue)>, JsException> {
+    unsafe {
+        let mut props: *mut JSPropertyEnum = std::ptr::null_mut();
+        let mut count = 0;
+        let flags = (JS_GPN_STRING_MASK | JS_GPN_ENUM_ONLY) as i32;
+        if JS_GetOwnPropertyNames(ctx, &mut props, &mut count, obj, flags) != 0 {
+            return Err(JsException(JsRef {
+                ctx,
+                v: js_exception(),
+            }));
+        }
+        let atoms: Vec<JSAtom> = (0..count as usize).map(|i| (*props.add(i)).atom).collect();
+        js_free(ctx, props.cast());
+
+        let mut entries = Vec::with_capacity(atoms.len());
+        let mut error = None;
+        for &atom in &atoms {
+            if error.is_none() {
+                let key = JsValue::from_qjs_value(ctx, JS_AtomToString(ctx, atom));
+                let value =
+                    JsValue::from_qjs_value(ctx, JS_GetPropertyInternal(ctx, obj, atom, obj, 0));
+                match (key, value) {
+                    (JsValue::Exception(e), _) | (_, JsValue::Exception(e)) => error = Some(e),
+                    (JsValue::String(key), value) => entries.push((key.to_string(), value)),
+                    _ => {}
+                }
+            }
+            JS_FreeAtom(ctx, atom);
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(entries),
+        }
+    }
+}
+
+// the index a property key stands for, like "3" but not "03"
+fn array_index(key: &str) -> Option<usize> {
+    match key.parse::<u32>() {
+        Ok(i) if i != u32::MAX && i.to_string() == key => Some(i as usize),
+        _ => None,
+    }
+}
+
+fn number(n: u32) -> JsValue {
+    match i32::try_from(n) {
+        Ok(n) => JsValue::Int(n),
+        Err(_) => JsValue::Float(n as f64),
+    }
+}
+
+// `obj[key] = value` as a new enumerable data property, so setters such as
+// `__proto__` are not triggered
+fn define_value(
+    ctx: *mut JSContext,
+    obj: JSValue,
+    key: JsValue,
+    value: JsValue,
+) -> Result<(), JsException> {
+    unsafe {
+        let flags = PropertyFlags::DEFAULT.define_bits(false);
+        let atom = JS_ValueToAtom(ctx, key.get_qjs_value());
+        if atom == JS_ATOM_NULL {
+            return Err(JsException(JsRef {
+                ctx,
+                v: js_exception(),
+            }));
+        }
+        let r = JS_DefinePropertyValue(ctx, obj, atom, value.into_qjs_value(), flags);
+        JS_FreeAtom(ctx, atom);
+        if r < 0 {
+            return Err(JsException(JsRef {
+                ctx,
+                v: js_exception(),
+            }));
+        }
+        Ok(())
+    }
+}
+
+// the little endian 64-bit digits of a decimal BigInt's magnitude
+fn decimal_to_digits(decimal: &str) -> Vec<u64> {
+    let mut digits: Vec<u64> = vec![];
+    for d in decimal.bytes().filter(u8::is_ascii_digit) {
+        let mut carry = (d - b'0') as u128;
+        for digit in digits.iter_mut() {
+            let x = *digit as u128 * 10 + carry;
+            *digit = x as u64;
+            carry = x >> 64;
+        }
+        if carry != 0 {
+            digits.push(carry as u64);
+        }
+    }
+    digits
+}
+
+fn digits_to_decimal(mut digits: Vec<u64>) -> String {
+    const CHUNK: u128 = 10_000_000_000_000_000_000;
+    let mut chunks = vec![];
+    while digits.iter().any(|&d| d != 0) {
+        let mut rem = 0_u128;
+        for digit in digits.iter_mut().rev() {
+            let x = (rem << 64) | *digit as u128;
+            *digit = (x / CHUNK) as u64;
+            rem = x % CHUNK;
+        }
+        chunks.push(rem as u64);
+    }
+    match chunks.split_last() {
+        None => "0".to_string(),
+        // the most significant chunk comes last and is not padded
+        Some((top, rest)) => rest.iter().rev().fold(top.to_string(), |mut s, chunk| {
+            s.push_str(&format!("{:019}", chunk));
+            s
+        }),
+    }
+}
+
+// what the message of a DataCloneError calls a value
+fn describe(value: &JsValue) -> String {
+    match value {
+        JsValue::Symbol(s) => format!("Symbol({})", s.description().unwrap_or_default()),
+        JsValue::Function(f) => match f.get("name") {
+            JsValue::String(name) => format!("function {}", name.to_string()),
+            _ => "function".to_string(),
+        },
+        JsValue::Object(obj) => match obj.get("constructor") {
+            JsValue::Function(f) => match f.get("name") {
+                JsValue::String(name) => format!("#<{}>", name.to_string()),
+                _ => "#<Object>".to_string(),
+            },
+            _ => "#<Object>".to_string(),
+        },
+        JsValue::Promise(_) => "#<Promise>".to_string(),
+        v => v.type_name().to_string(),
+    }
+}
+
+struct Serializer<'a> {
+    ctx: &'a mut Context,
+    buf: Vec<u8>,
+    ids: HashMap<usize, u32>,
+    transfer: &'a [JsArrayBuffer],
+    depth: usize,
+}
+
+impl<'a> Serializer<'a> {
+    fn write_varint(&mut self, mut n: u64) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    fn write_double(&mut self, n: f64) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    // Latin-1 when every char fits in a byte, UTF-16 otherwise
+    fn write_string(&mut self, s: &str) {
+        if s.chars().all(|c| (c as u32) < 0x100) {
+            self.buf.push(tag::ONE_BYTE_STRING);
+            self.write_varint(s.chars().count() as u64);
+            self.buf.extend(s.chars().map(|c| c as u8));
+        } else {
+            let units: Vec<u16> = s.encode_utf16().collect();
+            let len = units.len() as u64 * 2;
+            // V8 keeps the UTF-16 data 2-byte aligned
+            let varint_len = ((64 - len.leading_zeros() as usize).max(1) + 6) / 7;
+            if (self.buf.len() + 1 + varint_len) % 2 == 1 {
+                self.buf.push(tag::PADDING);
+            }
+            self.buf.push(tag::TWO_BYTE_STRING);
+            self.write_varint(len);
+            for unit in units {
+                self.buf.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+    }
+
+    // sign and byte length, then the digits of the magnitude
+    fn write_bigint(&mut self, decimal: &str) {
+        let negative = decimal.starts_with('-');
+        let digits = decimal_to_digits(decimal);
+        self.write_varint((digits.len() as u64 * 8) << 1 | negative as u64);
+        for digit in digits {
+            self.buf.extend_from_slice(&digit.to_le_bytes());
+        }
+    }
+
+    fn write_value(&mut self, value: &JsValue) -> Result<(), JsException> {
+        match value {
+            JsValue::UnDefined => self.buf.push(tag::UNDEFINED),
+            JsValue::Null => self.buf.push(tag::NULL),
+            JsValue::Bool(true) => self.buf.push(tag::TRUE),
+            JsValue::Bool(false) => self.buf.push(tag::FALSE),
+            JsValue::Int(n) => {
+                let n = *n;
+                self.buf.push(tag::INT32);
+                self.write_varint(((n << 1) ^ (n >> 31)) as u32 as u64);
+            }
+            JsValue::Float(n) => {
+                self.buf.push(tag::DOUBLE);
+                self.write_double(*n);
+            }
+            JsValue::String(s) => self.write_string(&s.to_string()),
+            JsValue::BigNum(n) if n.kind() == BigNumKind::BigInt => {
+                self.buf.push(tag::BIGINT);
+                self.write_bigint(&n.to_decimal_string());
+            }
+            JsValue::Exception(e) => return Err(e.clone()),
+            JsValue::Object(_)
+            | JsValue::Array(_)
+            | JsValue::ArrayBuffer(_)
+            | JsValue::TypedArray(_)
+            | JsValue::DataView(_)
+            | JsValue::Date(_)
+            | JsValue::RegExp(_)
+            | JsValue::Map(_)
+            | JsValue::Set(_) => return self.write_object(value),
+            _ => return Err(self.uncloneable(value)),
+        }
+        Ok(())
+    }
+
+    fn write_object(&mut self, value: &JsValue) -> Result<(), JsException> {
+        let v = value.get_qjs_value();
+        // a view writes its buffer first, so the buffer gets the lower id
+        let buffer = match value {
+            JsValue::TypedArray(array) => Some(array.buffer()),
+            JsValue::DataView(view) => Some(view.buffer()),
+            _ => None,
+        };
+        if let Some(buffer) = buffer {
+            if !self.ids.contains_key(&identity(v)) {
+                self.write_object(&JsValue::ArrayBuffer(buffer))?;
+            }
+        }
+
+        if let Some(&id) = self.ids.get(&identity(v)) {
+            self.buf.push(tag::OBJECT_REFERENCE);
+            self.write_varint(id as u64);
+            return Ok(());
+        }
+        let id = self.ids.len() as u32;
+        self.ids.insert(identity(v), id);
+
+        if self.depth == MAX_DEPTH {
+            return Err(self
+                .ctx
+                .throw_range_error("Maximum call stack size exceeded"));
+        }
+        self.depth += 1;
+        let r = match value {
+            JsValue::ArrayBuffer(buffer) => self.write_array_buffer(buffer),
+            JsValue::TypedArray(array) => {
+                self.write_view(Some(array.kind()), array.byte_offset(), array.byte_length());
+                Ok(())
+            }
+            JsValue::DataView(view) => {
+                self.write_view(None, view.byte_offset(), view.byte_length());
+                Ok(())
+            }
+            JsValue::Date(date) => {
+                self.buf.push(tag::DATE);
+                self.write_double(date.time_value());
+                Ok(())
+            }
+            JsValue::RegExp(regexp) => {
+                self.write_regexp(regexp);
+                Ok(())
+            }
+            JsValue::Map(map) => self.write_map(map),
+            JsValue::Set(set) => self.write_set(set),
+            JsValue::Array(array) => self.write_array(array),
+            JsValue::Object(obj) => match unsafe { JS_GetCloneKind_real(v) as u32 } {
+                JS_CLONE_KIND_PLAIN => self.write_plain_object(obj),
+                JS_CLONE_KIND_ERROR => self.write_error(obj),
+                JS_CLONE_KIND_PRIMITIVE_WRAPPER => self.write_primitive_wrapper(obj),
+                _ => Err(self.uncloneable(value)),
+            },
+            _ => Err(self.uncloneable(value)),
+        };
+        self.depth -= 1;
+        r
+    }
+
+    fn write_array_buffer(&mut self, buffer: &JsArrayBuffer) -> Result<(), JsException> {
+        let id = identity(buffer.0.v);
+        if let Some(i) = self.transfer.iter().position(|t| identity(t.0.v) == id) {
+            self.buf.push(tag::ARRAY_BUFFER_TRANSFER);
+            self.write_varint(i as u64);
+            return Ok(());
+        }
+        match array_buffer_bytes(buffer) {
+            Some(bytes) => {
+                self.buf.push(tag::ARRAY_BUFFER);
+                self.write_varint(bytes.len() as u64);
+                self.buf.extend_from_slice(bytes);
+                Ok(())
+            }
+            None => Err(self
+                .ctx
+                .throw_data_clone_error("An ArrayBuffer is detached and could not be cloned")),
+        }
+    }
+
+    fn write_view(&mut self, kind: Option<TypedArrayKind>, byte_offset: usize, byte_length: usize) {
+        let subtag = VIEW_SUBTAGS
+            .iter()
+            .find(|(_, k)| *k == kind)
+            .map_or(b'?', |(t, _)| *t);
+        self.buf.push(tag::ARRAY_BUFFER_VIEW);
+        self.buf.push(subtag);
+        self.write_varint(byte_offset as u64);
+        self.write_varint(byte_length as u64);
+        // flags, which only mark views of resizable buffers
+        self.write_varint(0);
+    }
+
+    fn write_regexp(&mut self, regexp: &JsRegExp) {
+        let flags = regexp.flags();
+        self.buf.push(tag::REGEXP);
+        self.write_string(&regexp.source());
+        self.write_varint(
+            REGEXP_FLAGS
+                .iter()
+                .filter(|(c, _)| flags.contains(*c))
+                .fold(0, |bits, (_, bit)| bits | bit),
+        );
+    }
+
+    fn write_map(&mut self, map: &JsMap) -> Result<(), JsException> {
+        let entries = map.entries()?;
+        self.buf.push(tag::BEGIN_MAP);
+        for (key, value) in &entries {
+            self.write_value(key)?;
+            self.write_value(value)?;
+        }
+        self.buf.push(tag::END_MAP);
+        self.write_varint(entries.len() as u64 * 2);
+        Ok(())
+    }
+
+    fn write_set(&mut self, set: &JsSet) -> Result<(), JsException> {
+        let values = set.values()?;
+        self.buf.push(tag::BEGIN_SET);
+        for value in &values {
+            self.write_value(value)?;
+        }
+        self.buf.push(tag::END_SET);
+        self.write_varint(values.len() as u64);
+        Ok(())
+    }
+
+    fn write_properties(&mut self, properties: &[(String, JsValue)]) -> Result<(), JsException> {
+        for (key, value) in properties {
+            self.write_string(key);
+            self.write_value(value)?;
+        }
+        Ok(())
+    }
+
+    // dense when every index has an element, sparse when there are holes
+    fn write_array(&mut self, array: &JsArray) -> Result<(), JsException> {
+        let len = array.get_length();
+        let mut elements = vec![];
+        let mut properties = vec![];
+        for (key, value) in own_entries(self.ctx.ctx, array.0.v)? {
+            match array_index(&key) {
+                Some(i) if i < len => elements.push((i, key, value)),
+                _ => properties.push((key, value)),
+            }
+        }
+        elements.sort_by_key(|(i, _, _)| *i);
+
+        if elements.len() == len {
+            self.buf.push(tag::BEGIN_DENSE_ARRAY);
+            self.write_varint(len as u64);
+            for (_, _, value) in &elements {
+                self.write_value(value)?;
+            }
+            self.write_properties(&properties)?;
+            self.buf.push(tag::END_DENSE_ARRAY);
+            self.write_varint(properties.len() as u64);
+        } else {
+            self.buf.push(tag::BEGIN_SPARSE_ARRAY);
+            self.write_varint(len as u64);
+            for (_, key, value) in &elements {
+                self.write_string(key);
+                self.write_value(value)?;
+            }
+            self.write_properties(&properties)?;
+            self.buf.push(tag::END_SPARSE_ARRAY);
+            self.write_varint((elements.len() + properties.len()) as u64);
+        }
+        self.write_varint(len as u64);
+        Ok(())
+    }
+
+    fn write_plain_object(&mut self, obj: &JsObject) -> Result<(), JsException> {
+        let properties = own_entries(self.ctx.ctx, obj.0.v)?;
+        self.buf.push(tag::BEGIN_OBJECT);
+        self.write_properties(&properties)?;
+        self.buf.push(tag::END_OBJECT);
+        self.write_varint(properties.len() as u64);
+        Ok(())
+    }
+
+    // the kind of error by its name, its own message and its stack
+    fn write_error(&mut self, error: &JsObject) -> Result<(), JsException> {
+        let name = match error.get("name") {
+            JsValue::String(name) => name.to_string(),
+            JsValue::Exception(e) => return Err(e),
+            _ => String::new(),
+        };
+        let message = match error.get_own_property("message")? {
+            Some(desc) if !desc.is_accessor() => match &desc.value {
+                JsValue::String(message) => Some(message.to_string()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let stack = match error.get("stack") {
+            JsValue::String(stack) => Some(stack.to_string()),
+            JsValue::Exception(e) => return Err(e),
+            _ => None,
+        };
+
+        self.buf.push(tag::ERROR);
+        if let Some((t, _)) = ERROR_PROTOTYPES.iter().find(|(_, n)| *n == name) {
+            self.buf.push(*t);
+        }
+        if let Some(message) = message {
+            self.buf.push(error_tag::MESSAGE);
+            self.write_string(&message);
+        }
+        if let Some(stack) = stack {
+            self.buf.push(error_tag::STACK);
+            self.write_string(&stack);
+        }
+        self.buf.push(error_tag::END);
+        Ok(())
+    }
+
+    // `new Number(1)` and friends
+    fn write_primitive_wrapper(&mut self, obj: &JsObject) -> Result<(), JsException> {
+        let ctx = self.ctx.ctx;
+        let value =
+            unsafe { JsValue::from_qjs_value(ctx, JS_GetPrimitiveWrapperValue_real(ctx, obj.0.v)) };
+        match value {
+            JsValue::Bool(true) => self.buf.push(tag::TRUE_OBJECT),
+            JsValue::Bool(false) => self.buf.push(tag::FALSE_OBJECT),
+            JsValue::Int(n) => {
+                self.buf.push(tag::NUMBER_OBJECT);
+                self.write_double(n as f64);
+            }
+            JsValue::Float(n) => {
+                self.buf.push(tag::NUMBER_OBJECT);
+                self.write_double(n);
+            }
+            JsValue::String(s) => {
+                self.buf.push(tag::STRING_OBJECT);
+                self.write_string(&s.to_string());
+            }
+            JsValue::BigNum(n) if n.kind() == BigNumKind::BigInt => {
+                self.buf.push(tag::BIGINT_OBJECT);
+                self.write_bigint(&n.to_decimal_string());
+            }
+            _ => return Err(self.uncloneable(&JsValue::Object(obj.clone()))),
+        }
+        Ok(())
+    }
+
+    fn uncloneable(&mut self, value: &JsValue) -> JsException {
+        let msg = format!("{} could not be cloned", describe(value));
+        self.ctx.throw_data_clone_error(&msg)
+    }
+}
+
+struct Deserializer<'a> {
+    ctx: &'a mut Context,
+    buf: &'a [u8],
+    pos: usize,
+    version: u64,
+    // every object read so far, by id
+    objects: Vec<JsValue>,
+    transferred: &'a [JsArrayBuffer],
+    depth: usize,
+}
+
+impl<'a> Deserializer<'a> {
+    fn invalid(&mut self) -> JsException {
+        let error = self.ctx.new_error("Unable to deserialize cloned data");
+        self.ctx.throw_error(error)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, JsException> {
+        match self.buf.get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                Ok(b)
+            }
+            None => Err(self.invalid()),
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], JsException> {
+        let buf: &'a [u8] = self.buf;
+        match buf.get(self.pos..).and_then(|rest| rest.get(..len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(self.invalid()),
+        }
+    }
+
+    fn read_varint(&mut self) -> Result<u64, JsException> {
+        let mut n = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.read_byte()?;
+            n |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(self.invalid())
+    }
+
+    fn read_u32(&mut self) -> Result<u32, JsException> {
+        match u32::try_from(self.read_varint()?) {
+            Ok(n) => Ok(n),
+            Err(_) => Err(self.invalid()),
+        }
+    }
+
+    fn read_double(&mut self) -> Result<f64, JsException> {
+        let bytes = self.read_bytes(8)?;
+        let mut le = [0; 8];
+        le.copy_from_slice(bytes);
+        Ok(f64::from_le_bytes(le))
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.buf[self.pos.min(self.buf.len())..]
+            .iter()
+            .copied()
+            .find(|&b| b != tag::PADDING)
+    }
+
+    fn read_tag(&mut self) -> Result<u8, JsException> {
+        loop {
+            match self.read_byte()? {
+                tag::PADDING => continue,
+                t => return Ok(t),
+            }
+        }
+    }
+
+    fn read_header(&mut self) -> Result<(), JsException> {
+        if self.read_byte()? != tag::VERSION {
+            return Err(self.invalid());
+        }
+        self.version = self.read_varint()?;
+        if self.version > VERSION {
+            return Err(self.invalid());
+        }
+        Ok(())
+    }
+
+    fn read_value(&mut self) -> Result<JsValue, JsException> {
+        if self.depth == MAX_DEPTH {
+            return Err(self
+                .ctx
+                .throw_range_error("Maximum call stack size exceeded"));
+        }
+        self.depth += 1;
+        let value = self.read_value_inner();
+        self.depth -= 1;
+
+        // a view follows the buffer it views
+        match value? {
+            JsValue::ArrayBuffer(buffer) if self.peek_tag() == Some(tag::ARRAY_BUFFER_VIEW) => {
+                self.read_tag()?;
+                self.read_view(&buffer)
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn read_value_inner(&mut self) -> Result<JsValue, JsException> {
+        match self.read_tag()? {
+            tag::VERIFY_OBJECT_COUNT => {
+                self.read_varint()?;
+                self.read_value_inner()
+            }
+            tag::UNDEFINED => Ok(JsValue::UnDefined),
+            tag::NULL => Ok(JsValue::Null),
+            tag::TRUE => Ok(JsValue::Bool(true)),
+            tag::FALSE => Ok(JsValue::Bool(false)),
+            tag::INT32 => {
+                let n = self.read_u32()?;
+                Ok(JsValue::Int(((n >> 1) as i32) ^ -((n & 1) as i32)))
+            }
+            tag::UINT32 => Ok(number(self.read_u32()?)),
+            tag::DOUBLE => Ok(JsValue::Float(self.read_double()?)),
+            tag::BIGINT => self.read_bigint(),
+            tag::UTF8_STRING => {
+                let len = self.read_u32()? as usize;
+                match std::str::from_utf8(self.read_bytes(len)?) {
+                    Ok(s) => Ok(self.ctx.new_string(s).into()),
+                    Err(_) => Err(self.invalid()),
+                }
+            }
+            tag::ONE_BYTE_STRING => {
+                let len = self.read_u32()? as usize;
+                let s: String = self.read_bytes(len)?.iter().map(|&b| b as char).collect();
+                Ok(self.ctx.new_string(&s).into())
+            }
+            tag::TWO_BYTE_STRING => {
+                let len = self.read_u32()? as usize;
+                if len % 2 != 0 {
+                    return Err(self.invalid());
+                }
+                let units: Vec<u16> = self
+                    .read_bytes(len)?
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                Ok(self
+                    .ctx
+                    .new_string(&String::from_utf16_lossy(&units))
+                    .into())
+            }
+            tag::OBJECT_REFERENCE => {
+                let id = self.read_u32()? as usize;
+                match self.objects.get(id) {
+                    Some(obj) => Ok(obj.clone()),
+                    None => Err(self.invalid()),
+                }
+            }
+            tag::BEGIN_OBJECT => {
+                let obj: JsValue = self.ctx.new_object().into();
+                self.objects.push(obj.clone());
+                self.read_properties(&obj, tag::END_OBJECT)?;
+                self.read_varint()?;
+                Ok(obj)
+            }
+            tag::BEGIN_DENSE_ARRAY => {
+                let len = self.read_u32()?;
+                let mut array = self.ctx.new_array();
+                array.set_length(len as usize);
+                let value: JsValue = array.clone().into();
+                self.objects.push(value.clone());
+                for i in 0..len {
+                    if self.peek_tag() == Some(tag::THE_HOLE) {
+                        self.read_tag()?;
+                        continue;
+                    }
+                    let element = self.read_value()?;
+                    define_value(self.ctx.ctx, array.0.v, number(i), element)?;
+                }
+                self.read_properties(&value, tag::END_DENSE_ARRAY)?;
+                self.read_varint()?;
+                self.read_varint()?;
+                Ok(value)
+            }
+            tag::BEGIN_SPARSE_ARRAY => {
+                let len = self.read_u32()?;
+                let mut array = self.ctx.new_array();
+                array.set_length(len as usize);
+                let value: JsValue = array.into();
+                self.objects.push(value.clone());
+                self.read_properties(&value, tag::END_SPARSE_ARRAY)?;
+                self.read_varint()?;
+                self.read_varint()?;
+                Ok(value)
+            }
+            tag::DATE => {
+                let ms = self.read_double()?;
+                let date: JsValue = self.ctx.new_date(ms).into();
+                self.objects.push(date.clone());
+                Ok(date)
+            }
+            tag::TRUE_OBJECT => self.read_primitive_wrapper(JsValue::Bool(true)),
+            tag::FALSE_OBJECT => self.read_primitive_wrapper(JsValue::Bool(false)),
+            tag::NUMBER_OBJECT => {
+                let n = self.read_double()?;
+                self.read_primitive_wrapper(JsValue::Float(n))
+            }
+            tag::BIGINT_OBJECT => {
+                let n = self.read_bigint()?;
+                self.read_primitive_wrapper(n)
+            }
+            tag::STRING_OBJECT => {
+                let s = self.read_string()?;
+                self.read_primitive_wrapper(s.into())
+            }
+            tag::REGEXP => {
+                let source = self.read_string()?.to_string();
+                let bits = self.read_varint()?;
+                let flags: String = REGEXP_FLAGS
+                    .iter()
+                    .filter(|(_, bit)| bits & bit != 0)
+                    .map(|(c, _)| *c)
+                    .collect();
+                match self.ctx.new_regexp(&source, &flags) {
+                    JsValue::Exception(e) => Err(e),
+                    regexp => {
+                        self.objects.push(regexp.clone());
+                        Ok(regexp)
+                    }
+                }
+            }
+            tag::BEGIN_MAP => {
+                let mut map = self.ctx.new_map();
+                self.objects.push(map.clone().into());
+                while self.peek_tag() != Some(tag::END_MAP) {
+                    let key = self.read_value()?;
+                    let value = self.read_value()?;
+                    if let JsValue::Exception(e) = map.insert(key, value) {
+                        return Err(e);
+                    }
+                }
+                self.read_tag()?;
+                self.read_varint()?;
+                Ok(map.into())
+            }
+            tag::BEGIN_SET => {
+                let mut set = self.ctx.new_set();
+                self.objects.push(set.clone().into());
+                while self.peek_tag() != Some(tag::END_SET) {
+                    let value = self.read_value()?;
+                    if let JsValue::Exception(e) = set.insert(value) {
+                        return Err(e);
+                    }
+                }
+                self.read_tag()?;
+                self.read_varint()?;
+                Ok(set.into())
+            }
+            tag::ARRAY_BUFFER => {
+                let len = self.read_u32()? as usize;
+                let bytes = self.read_bytes(len)?;
+                let buffer: JsValue = self.ctx.new_array_buffer(bytes).into();
+                self.objects.push(buffer.clone());
+                Ok(buffer)
+            }
+            tag::ARRAY_BUFFER_TRANSFER => {
+                let i = self.read_u32()? as usize;
+                match self.transferred.get(i) {
+                    Some(buffer) => {
+                        let buffer = JsValue::ArrayBuffer(buffer.clone());
+                        self.objects.push(buffer.clone());
+                        Ok(buffer)
+                    }
+                    None => Err(self.invalid()),
+                }
+            }
+            tag::ERROR => self.read_error(),
+            _ => Err(self.invalid()),
+        }
+    }
+
+    // a string with its tag, where only a string may appear
+    fn read_string(&mut self) -> Result<JsString, JsException> {
+        match self.read_value()? {
+            JsValue::String(s) => Ok(s),
+            _ => Err(self.invalid()),
+        }
+    }
+
+    fn read_bigint(&mut self) -> Result<JsValue, JsException> {
+        let bitfield = self.read_varint()?;
+        let len = usize::try_from(bitfield >> 1).unwrap_or(usize::MAX);
+        let digits = self
+            .read_bytes(len)?
+            .chunks(8)
+            .map(|chunk| {
+                let mut le = [0; 8];
+                le[..chunk.len()].copy_from_slice(chunk);
+                u64::from_le_bytes(le)
+            })
+            .collect();
+        let sign = if bitfield & 1 == 1 { "-" } else { "" };
+        let decimal = format!("{}{}", sign, digits_to_decimal(digits));
+        match self.ctx.new_bignum_from_str(BigNumKind::BigInt, &decimal) {
+            JsValue::Exception(e) => Err(e),
+            n => Ok(n),
+        }
+    }
+
+    fn read_primitive_wrapper(&mut self, value: JsValue) -> Result<JsValue, JsException> {
+        let ctx = self.ctx.ctx;
+        let obj = unsafe {
+            JsValue::from_qjs_value(ctx, JS_NewPrimitiveWrapper_real(ctx, value.get_qjs_value()))
+        };
+        if let JsValue::Exception(e) = obj {
+            return Err(e);
+        }
+        self.objects.push(obj.clone());
+        Ok(obj)
+    }
+
+    fn read_view(&mut self, buffer: &JsArrayBuffer) -> Result<JsValue, JsException> {
+        let subtag = self.read_byte()?;
+        let byte_offset = self.read_varint()? as usize;
+        let byte_length = self.read_varint()? as usize;
+        if self.version >= VERSION_VIEW_FLAGS {
+            self.read_varint()?;
+        }
+        let view = match VIEW_SUBTAGS.iter().find(|(t, _)| *t == subtag) {
+            Some((_, Some(kind))) => {
+                let size = kind.bytes_per_element();
+                if byte_length % size != 0 {
+                    return Err(self.invalid());
+                }
+                self.ctx
+                    .new_typed_array_view(*kind, buffer, byte_offset, byte_length / size)
+            }
+            Some((_, None)) => self.ctx.new_data_view(buffer, byte_offset, byte_length),
+            None => return Err(self.invalid()),
+        };
+        if let JsValue::Exception(e) = view {
+            return Err(e);
+        }
+        self.objects.push(view.clone());
+        Ok(view)
+    }
+
+    // key and value pairs up to `end`, with keys that are strings or numbers
+    fn read_properties(&mut self, obj: &JsValue, end: u8) -> Result<(), JsException> {
+        while self.peek_tag() != Some(end) {
+            let key = self.read_value()?;
+            if let JsValue::String(_) | JsValue::Int(_) | JsValue::Float(_) = key {
+                let value = self.read_value()?;
+                define_value(self.ctx.ctx, obj.get_qjs_value(), key, value)?;
+            } else {
+                return Err(self.invalid());
+            }
+        }
+        self.read_tag()?;
+        Ok(())
+    }
+
+    fn read_error(&mut self) -> Result<JsValue, JsException> {
+        // the id is taken before a cause, which may hold objects of its own
+        let id = self.objects.len();
+        self.objects.push(JsValue::UnDefined);
+
+        let mut constructor = "Error";
+        let mut properties = vec![];
+        loop {
+            match self.read_byte()? {
+                error_tag::END => break,
+                error_tag::MESSAGE => properties.push(("message", self.read_string()?.into())),
+                error_tag::STACK => properties.push(("stack", self.read_string()?.into())),
+                error_tag::CAUSE => properties.push(("cause", self.read_value()?)),
+                t => match ERROR_PROTOTYPES.iter().find(|(p, _)| *p == t) {
+                    Some((_, name)) => constructor = *name,
+                    None => return Err(self.invalid()),
+                },
+            }
+        }
+
+        let mut error = match self.ctx.construct_global(constructor, &[]) {
+            JsValue::Object(error) => error,
+            JsValue::Exception(e) => return Err(e),
+            _ => return Err(self.invalid()),
+        };
+        for (key, value) in properties {
+            error.define_property(key, value, PropertyFlags::HIDDEN)?;
+        }
+        let error = JsValue::Object(error);
+        self.objects[id] = error.clone();
+        Ok(error)
+    }
+}
+
+impl Context {
+    /// `v8.serialize(value)`: `value` in the wire format of V8's
+    /// `ValueSerializer`, which `v8.deserialize` in Node can read back as long
+    /// as no typed array is involved, since Node writes those its own way.
+    ///
+    /// Throws a `DataCloneError` for what the structured clone algorithm
+    /// can't copy, like functions, symbols, promises and weak collections.
+    pub fn serialize_value(&mut self, value: &JsValue) -> Result<Vec<u8>, JsException> {
+        self.serialize_with_transfer(value, &[])
+    }
+
+    /// `v8.deserialize(bytes)`. Throws an `Error` when `bytes` are not
+    /// something `serialize_value` or V8 wrote.
+    pub fn deserialize_value(&mut self, bytes: &[u8]) -> Result<JsValue, JsException> {
+        self.deserialize_with_transfer(bytes, &[])
+    }
+
+    /// `structuredClone(value, { transfer })`: a deep copy of `value` that
+    /// keeps cycles and shared references. The memory of the buffers in
+    /// `transfer` is handed to the copy without copying it, which leaves the
+    /// originals detached.
+    pub fn structured_clone(
+        &mut self,
+        value: &JsValue,
+        transfer: &[JsArrayBuffer],
+    ) -> Result<JsValue, JsException> {
+        for (i, buffer) in transfer.iter().enumerate() {
+            let id = identity(buffer.0.v);
+            if transfer[..i].iter().any(|b| identity(b.0.v) == id) {
+                let msg = format!("ArrayBuffer at index {} is a duplicate", i);
+                return Err(self.throw_data_clone_error(&msg));
+            }
+            if array_buffer_bytes(buffer).is_none() {
+                let msg = format!("ArrayBuffer at index {} is already detached", i);
+                return Err(self.throw_data_clone_error(&msg));
+            }
+        }
+
+        let bytes = self.serialize_with_transfer(value, transfer)?;
+        let mut transferred = Vec::with_capacity(transfer.len());
+        for buffer in transfer {
+            // the memory itself changes hands, nothing is copied
+            let moved = unsafe {
+                let v = JS_TransferArrayBuffer_real(self.ctx, buffer.0.v);
+                JsValue::from_qjs_value(self.ctx, v)
+            };
+            match moved {
+                JsValue::ArrayBuffer(moved) => transferred.push(moved),
+                JsValue::Exception(e) => return Err(e),
+                _ => {
+                    return Err(self.throw_data_clone_error("ArrayBuffer could not be transferred"))
+                }
+            }
+        }
+        self.deserialize_with_transfer(&bytes, &transferred)
+    }
+
+    /// Throws the `DataCloneError` of a value that can't be cloned or
+    /// transferred. QuickJS has no `DOMException`, so it is an `Error` with
+    /// that name.
+    pub fn throw_data_clone_error(&mut self, msg: &str) -> JsException {
+        let name = self.new_string("DataCloneError");
+        let mut error = self.new_error(msg);
+        if let JsValue::Object(e) = &mut error {
+            e.set("name", name.into());
+        }
+        self.throw_error(error)
+    }
+
+    fn serialize_with_transfer(
+        &mut self,
+        value: &JsValue,
+        transfer: &[JsArrayBuffer],
+    ) -> Result<Vec<u8>, JsException> {
+        let mut serializer = Serializer {
+            ctx: self,
+            buf: vec![tag::VERSION],
+            ids: HashMap::new(),
+            transfer,
+            depth: 0,
+        };
+        serializer.write_varint(VERSION);
+        serializer.write_value(value)?;
+        Ok(serializer.buf)
+    }
+
+    fn deserialize_with_transfer(
+        &mut self,
+        bytes: &[u8],
+        transferred: &[JsArrayBuffer],
+    ) -> Result<JsValue, JsException> {
+        let mut deserializer = Deserializer {
+            ctx: self,
+            buf: bytes,
+            pos: 0,
+            version: 0,
+            objects: vec![],
+            transferred,
+            depth: 0,
+        };
+        deserializer.read_header()?;
+        deserializer.read_value()
+    }
+}
@@ -0,0 +1,122 @@
+use super::qjs::*;
+use super::{AsObject, Context, JsException, JsRef, JsValue};
+
+/// The symbols kept on the global `Symbol`, like `Symbol.iterator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WellKnownSymbol {
+    AsyncIterator,
+    HasInstance,
+    IsConcatSpreadable,
+    Iterator,
+    Match,
+    MatchAll,
+    Replace,
+    Search,
+    Species,
+    Split,
+    ToPrimitive,
+    ToStringTag,
+    Unscopables,
+}
+
+impl WellKnownSymbol {
+    /// The property of `Symbol` holding it.
+    pub fn name(self) -> &'static str {
+        match self {
+            WellKnownSymbol::AsyncIterator => "asyncIterator",
+            WellKnownSymbol::HasInstance => "hasInstance",
+            WellKnownSymbol::IsConcatSpreadable => "isConcatSpreadable",
+            WellKnownSymbol::Iterator => "iterator",
+            WellKnownSymbol::Match => "match",
+            WellKnownSymbol::MatchAll => "matchAll",
+            WellKnownSymbol::Replace => "replace",
+            WellKnownSymbol::Search => "search",
+            WellKnownSymbol::Species => "species",
+            WellKnownSymbol::Split => "split",
+            WellKnownSymbol::ToPrimitive => "toPrimitive",
+            WellKnownSymbol::ToStringTag => "toStringTag",
+            WellKnownSymbol::Unscopables => "unscopables",
+        }
+    }
+
+    // the order of the atoms in quickjs-atom.h
+    fn atom_offset(self) -> i32 {
+        match self {
+            WellKnownSymbol::ToPrimitive => 0,
+            WellKnownSymbol::Iterator => 1,
+            WellKnownSymbol::Match => 2,
+            WellKnownSymbol::MatchAll => 3,
+            WellKnownSymbol::Replace => 4,
+            WellKnownSymbol::Search => 5,
+            WellKnownSymbol::Split => 6,
+            WellKnownSymbol::ToStringTag => 7,
+            WellKnownSymbol::IsConcatSpreadable => 8,
+            WellKnownSymbol::HasInstance => 9,
+            WellKnownSymbol::Species => 10,
+            WellKnownSymbol::Unscopables => 11,
+            WellKnownSymbol::AsyncIterator => 12,
+        }
+    }
+}
+
+/// A `Symbol`, usable as a property key with `AsObject::get_symbol` and
+/// `AsObject::set_symbol`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsSymbol(pub(super) JsRef);
+
+impl JsSymbol {
+    /// `symbol.description`, `None` for `Symbol()`.
+    pub fn description(&self) -> Option<String> {
+        unsafe {
+            let v = JS_GetPropertyStr(self.0.ctx, self.0.v, "description\0".as_ptr().cast());
+            match JsValue::from_qjs_value(self.0.ctx, v) {
+                JsValue::String(s) => Some(s.to_string()),
+                _ => None,
+            }
+        }
+    }
+
+    pub(super) fn to_atom(&self) -> JSAtom {
+        unsafe { JS_ValueToAtom(self.0.ctx, self.0.v) }
+    }
+}
+
+impl Context {
+    /// `Symbol(description)`, a new symbol unlike any other. Throws when the
+    /// global `Symbol` was replaced by something that doesn't return one.
+    pub fn new_symbol(&mut self, description: &str) -> Result<JsSymbol, JsException> {
+        let description = self.new_string(description);
+        self.call_symbol(None, description.into())
+    }
+
+    /// `Symbol.for(key)`, the same symbol for the same key across realms.
+    /// Throws like `new_symbol`.
+    pub fn symbol_for(&mut self, key: &str) -> Result<JsSymbol, JsException> {
+        let key = self.new_string(key);
+        self.call_symbol(Some("for"), key.into())
+    }
+
+    /// The symbol itself, not what the global `Symbol` currently holds.
+    pub fn well_known_symbol(&mut self, symbol: WellKnownSymbol) -> JsSymbol {
+        unsafe {
+            let v = JS_GetWellKnownSymbol_real(self.ctx, symbol.atom_offset());
+            JsSymbol(JsRef { ctx: self.ctx, v })
+        }
+    }
+
+    fn call_symbol(&mut self, method: Option<&str>, arg: JsValue) -> Result<JsSymbol, JsException> {
+        let v = match (self.get_global().get("Symbol"), method) {
+            (JsValue::Function(f), None) => f.call(&[arg]),
+            (JsValue::Function(mut f), Some(method)) => f.invoke(method, &[arg]),
+            _ => JsValue::UnDefined,
+        };
+        match v {
+            JsValue::Symbol(s) => Ok(s),
+            JsValue::Exception(e) => Err(e),
+            v => {
+                let msg = format!("Symbol returned {} instead of a symbol", v.type_name());
+                Err(self.throw_type_error(&msg))
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use super::qjs::*;
+use super::{Context, JsArrayBuffer, JsRef, JsValue};
+
+/// The element type of a typed array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypedArrayKind {
+    Uint8Clamped,
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    BigInt64,
+    BigUint64,
+    Float32,
+    Float64,
+}
+
+impl TypedArrayKind {
+    fn from_buffer_kind(kind: u32) -> Option<Self> {
+        Some(match kind {
+            JS_BUFFER_KIND_UINT8C_ARRAY => TypedArrayKind::Uint8Clamped,
+            JS_BUFFER_KIND_INT8_ARRAY => TypedArrayKind::Int8,
+            JS_BUFFER_KIND_UINT8_ARRAY => TypedArrayKind::Uint8,
+            JS_BUFFER_KIND_INT16_ARRAY => TypedArrayKind::Int16,
+            JS_BUFFER_KIND_UINT16_ARRAY => TypedArrayKind::Uint16,
+            JS_BUFFER_KIND_INT32_ARRAY => TypedArrayKind::Int32,
+            JS_BUFFER_KIND_UINT32_ARRAY => TypedArrayKind::Uint32,
+            JS_BUFFER_KIND_BIG_INT64_ARRAY => TypedArrayKind::BigInt64,
+            JS_BUFFER_KIND_BIG_UINT64_ARRAY => TypedArrayKind::BigUint64,
+            JS_BUFFER_KIND_FLOAT32_ARRAY => TypedArrayKind::Float32,
+            JS_BUFFER_KIND_FLOAT64_ARRAY => TypedArrayKind::Float64,
+            _ => return None,
+        })
+    }
+
+    fn buffer_kind(self) -> u32 {
+        match self {
+            TypedArrayKind::Uint8Clamped => JS_BUFFER_KIND_UINT8C_ARRAY,
+            TypedArrayKind::Int8 => JS_BUFFER_KIND_INT8_ARRAY,
+            TypedArrayKind::Uint8 => JS_BUFFER_KIND_UINT8_ARRAY,
+            TypedArrayKind::Int16 => JS_BUFFER_KIND_INT16_ARRAY,
+            TypedArrayKind::Uint16 => JS_BUFFER_KIND_UINT16_ARRAY,
+            TypedArrayKind::Int32 => JS_BUFFER_KIND_INT32_ARRAY,
+            TypedArrayKind::Uint32 => JS_BUFFER_KIND_UINT32_ARRAY,
+            TypedArrayKind::BigInt64 => JS_BUFFER_KIND_BIG_INT64_ARRAY,
+            TypedArrayKind::BigUint64 => JS_BUFFER_KIND_BIG_UINT64_ARRAY,
+            TypedArrayKind::Float32 => JS_BUFFER_KIND_FLOAT32_ARRAY,
+            TypedArrayKind::Float64 => JS_BUFFER_KIND_FLOAT64_ARRAY,
+        }
+    }
+
+    pub fn bytes_per_element(self) -> usize {
+        match self {
+            TypedArrayKind::Uint8Clamped | TypedArrayKind::Int8 | TypedArrayKind::Uint8 => 1,
+            TypedArrayKind::Int16 | TypedArrayKind::Uint16 => 2,
+            TypedArrayKind::Int32 | TypedArrayKind::Uint32 | TypedArrayKind::Float32 => 4,
+            TypedArrayKind::BigInt64 | TypedArrayKind::BigUint64 | TypedArrayKind::Float64 => 8,
+        }
+    }
+
+    /// The name of the JS constructor, e.g. `Uint8Array`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TypedArrayKind::Uint8Clamped => "Uint8ClampedArray",
+            TypedArrayKind::Int8 => "Int8Array",
+            TypedArrayKind::Uint8 => "Uint8Array",
+            TypedArrayKind::Int16 => "Int16Array",
+            TypedArrayKind::Uint16 => "Uint16Array",
+            TypedArrayKind::Int32 => "Int32Array",
+            TypedArrayKind::Uint32 => "Uint32Array",
+            TypedArrayKind::BigInt64 => "BigInt64Array",
+            TypedArrayKind::BigUint64 => "BigUint64Array",
+            TypedArrayKind::Float32 => "Float32Array",
+            TypedArrayKind::Float64 => "Float64Array",
+        }
+    }
+}
+
+/// Rust types a typed array can be viewed as.
+pub trait TypedArrayElement: Copy + 'static {
+    const KIND: TypedArrayKind;
+}
+
+macro_rules! impl_typed_array_element {
+    ($($t:ty => $kind:ident),*) => {
+        $(
+            impl TypedArrayElement for $t {
+                const KIND: TypedArrayKind = TypedArrayKind::$kind;
+            }
+        )*
+    };
+}
+
+impl_typed_array_element!(
+    u8 => Uint8,
+    i8 => Int8,
+    i16 => Int16,
+    u16 => Uint16,
+    i32 => Int32,
+    u32 => Uint32,
+    i64 => BigInt64,
+    u64 => BigUint64,
+    f32 => Float32,
+    f64 => Float64
+);
+
+pub(super) fn buffer_kind(v: JSValue) -> u32 {
+    unsafe { JS_GetBufferKind_real(v) as u32 }
+}
+
+fn typed_array_kind(v: JSValue) -> Option<TypedArrayKind> {
+    TypedArrayKind::from_buffer_kind(buffer_kind(v))
+}
+
+// (data, byte_offset, byte_length), `data` is null once the buffer is detached
+fn view_range(r: &JsRef) -> (*mut u8, usize, usize) {
+    unsafe {
+        let mut offset = 0;
+        let mut len = 0;
+        let data = JS_GetArrayBufferView_real(r.v, &mut offset, &mut len);
+        (data, offset, len)
+    }
+}
+
+fn view_bytes(r: &JsRef) -> &[u8] {
+    match view_range(r) {
+        (data, _, _) if data.is_null() => &[],
+        (data, offset, len) => unsafe { std::slice::from_raw_parts(data.add(offset), len) },
+    }
+}
+
+fn view_bytes_mut(r: &mut JsRef) -> &mut [u8] {
+    match view_range(r) {
+        (data, _, _) if data.is_null() => &mut [],
+        (data, offset, len) => unsafe { std::slice::from_raw_parts_mut(data.add(offset), len) },
+    }
+}
+
+fn view_buffer(r: &JsRef) -> JsArrayBuffer {
+    unsafe {
+        let v = JS_GetArrayBufferViewBuffer_real(r.ctx, r.v);
+        JsArrayBuffer(JsRef { ctx: r.ctx, v })
+    }
+}
+
+/// A `Uint8Array`, `Float64Array` or any other typed array.
+///
+/// The slices borrow the memory of the underlying `ArrayBuffer` without
+/// copying it. They are only valid until JS runs again, which may detach the
+/// buffer; a detached buffer reads as empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsTypedArray(pub(super) JsRef);
+
+impl JsTypedArray {
+    pub fn kind(&self) -> TypedArrayKind {
+        typed_array_kind(self.0.v).unwrap_or(TypedArrayKind::Uint8)
+    }
+
+    pub fn byte_offset(&self) -> usize {
+        view_range(&self.0).1
+    }
+
+    pub fn byte_length(&self) -> usize {
+        view_range(&self.0).2
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> usize {
+        self.byte_length() / self.kind().bytes_per_element()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.byte_length() == 0
+    }
+
+    pub fn buffer(&self) -> JsArrayBuffer {
+        view_buffer(&self.0)
+    }
+
+    /// The elements, if they are of type `T`. A `Uint8ClampedArray` can be
+    /// viewed as `u8` too.
+    pub fn as_slice<T: TypedArrayElement>(&self) -> Option<&[T]> {
+        if !self.is_kind_of::<T>() {
+            return None;
+        }
+        let bytes = view_bytes(&self.0);
+        let len = bytes.len() / std::mem::size_of::<T>();
+        // typed arrays start at a multiple of their element size
+        Some(unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast(), len) })
+    }
+
+    pub fn as_mut_slice<T: TypedArrayElement>(&mut self) -> Option<&mut [T]> {
+        if !self.is_kind_of::<T>() {
+            return None;
+        }
+        let bytes = view_bytes_mut(&mut self.0);
+        let len = bytes.len() / std::mem::size_of::<T>();
+        Some(unsafe { std::slice::from_raw_parts_mut(bytes.as_mut_ptr().cast(), len) })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+
+    fn is_kind_of<T: TypedArrayElement>(&self) -> bool {
+        let kind = self.kind();
+        kind == T::KIND
+            || (kind == TypedArrayKind::Uint8Clamped && T::KIND == TypedArrayKind::Uint8)
+    }
+}
+
+/// The bytes the array views, whatever its element type.
+impl AsRef<[u8]> for JsTypedArray {
+    fn as_ref(&self) -> &[u8] {
+        view_bytes(&self.0)
+    }
+}
+
+impl AsMut<[u8]> for JsTypedArray {
+    fn as_mut(&mut self) -> &mut [u8] {
+        view_bytes_mut(&mut self.0)
+    }
+}
+
+/// A `DataView`. Its slices behave like the ones of `JsTypedArray`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsDataView(pub(super) JsRef);
+
+impl JsDataView {
+    pub fn byte_offset(&self) -> usize {
+        view_range(&self.0).1
+    }
+
+    pub fn byte_length(&self) -> usize {
+        view_range(&self.0).2
+    }
+
+    pub fn buffer(&self) -> JsArrayBuffer {
+        view_buffer(&self.0)
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+}
+
+impl AsRef<[u8]> for JsDataView {
+    fn as_ref(&self) -> &[u8] {
+        view_bytes(&self.0)
+    }
+}
+
+impl AsMut<[u8]> for JsDataView {
+    fn as_mut(&mut self) -> &mut [u8] {
+        view_bytes_mut(&mut self.0)
+    }
+}
+
+impl JsValue {
+    /// The bytes of an `ArrayBuffer`, typed array or `DataView`, without
+    /// copying them.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            JsValue::ArrayBuffer(buf) => Some(buf.as_ref()),
+            JsValue::TypedArray(array) => Some(array.as_ref()),
+            JsValue::DataView(view) => Some(view.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        match self {
+            JsValue::ArrayBuffer(buf) => Some(buf.as_mut()),
+            JsValue::TypedArray(array) => Some(array.as_mut()),
+            JsValue::DataView(view) => Some(view.as_mut()),
+            _ => None,
+        }
+    }
+}
+
+impl Context {
+    /// A new `Uint8Array` holding a copy of `bytes`.
+    pub fn new_uint8_array(&mut self, bytes: &[u8]) -> JsTypedArray {
+        self.new_typed_array(bytes)
+    }
+
+    /// A new typed array of `T`'s kind holding a copy of `items`, e.g. a
+    /// `Float64Array` for `&[f64]`.
+    pub fn new_typed_array<T: TypedArrayElement>(&mut self, items: &[T]) -> JsTypedArray {
+        let buffer = self.new_array_buffer_t(items);
+        unsafe {
+            let v = JS_NewArrayBufferView_real(
+                self.ctx,
+                T::KIND.buffer_kind() as i32,
+                buffer.0.v,
+                0,
+                items.len(),
+            );
+            JsTypedArray(JsRef { ctx: self.ctx, v })
+        }
+    }
+
+    /// `new <kind>(buffer, byte_offset, len)`, sharing the memory of
+    /// `buffer`. Throws a `RangeError` when the range is out of bounds or
+    /// misaligned.
+    pub fn new_typed_array_view(
+        &mut self,
+        kind: TypedArrayKind,
+        buffer: &JsArrayBuffer,
+        byte_offset: usize,
+        len: usize,
+    ) -> JsValue {
+        unsafe {
+            let v = JS_NewArrayBufferView_real(
+                self.ctx,
+                kind.buffer_kind() as i32,
+                buffer.0.v,
+                byte_offset,
+                len,
+            );
+            JsValue::from_qjs_value(self.ctx, v)
+        }
+    }
+
+    /// `new DataView(buffer, byte_offset, byte_length)`.
+    pub fn new_data_view(
+        &mut self,
+        buffer: &JsArrayBuffer,
+        byte_offset: usize,
+        byte_length: usize,
+    ) -> JsValue {
+        unsafe {
+            let v = JS_NewArrayBufferView_real(
+                self.ctx,
+                JS_BUFFER_KIND_DATA_VIEW as i32,
+                buffer.0.v,
+                byte_offset,
+                byte_length,
+            );
+            JsValue::from_qjs_value(self.ctx, v)
+        }
+    }
+}
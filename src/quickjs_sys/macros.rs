@@ -99,6 +99,15 @@ macro_rules! assert_size_zero {
 
         let _ = AssertSize::<$d, $getter, $setter>::GETTER_SETTER_SIZE_MUST_ZERO;
     }};
+    (@typed,$a:tt,$r:tt,$t:tt) => {{
+        struct AssertSize<A, R, F: Fn(&mut Context, A) -> R>(PhantomData<(A, R, F)>);
+        impl<A, R, F: Fn(&mut Context, A) -> R> AssertSize<A, R, F> {
+            const ASSERT: [(); 1] = [()];
+            const F_SIZE_MUST_ZERO: () = Self::ASSERT[std::mem::size_of::<F>()];
+        }
+
+        let _ = AssertSize::<$a, $r, $t>::F_SIZE_MUST_ZERO;
+    }};
     (@module,$t:tt) => {{
         struct AssertSize<F: Fn(&mut Context, &mut JsModuleDef)>(PhantomData<F>);
         impl<F: Fn(&mut Context, &mut JsModuleDef)> AssertSize<F> {
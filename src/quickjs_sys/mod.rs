@@ -1,14 +1,43 @@
 #[macro_use]
 mod macros;
+pub mod context_builder;
+pub mod js_async;
+pub mod js_bignum;
+pub mod js_builtins;
+pub mod js_bytecode;
 pub mod js_class;
+pub mod js_closure;
+pub mod js_convert;
+pub mod js_error;
+pub mod js_iterator;
+pub mod js_json;
 pub mod js_module;
+pub mod js_property;
+mod js_rejection;
+#[cfg(feature = "serde")]
+pub mod js_serde;
+pub mod js_structured_clone;
+pub mod js_symbol;
+pub mod js_typed_array;
 pub mod resolver;
+pub mod source_map;
 pub mod transpiler;
 
 use std::collections::HashMap;
 
+pub use context_builder::ContextBuilder;
+pub use js_bignum::{BigInt, BigNumKind};
+pub use js_builtins::{JsDate, JsMap, JsRegExp, JsSet};
 pub use js_class::*;
+pub use js_convert::*;
+pub use js_error::{JsError, JsErrorKind};
+pub use js_iterator::{JsAsyncIterator, JsIterator};
 pub use js_module::{JsModuleDef, ModuleInit};
+pub use js_property::{PropertyDescriptor, PropertyFlags, PropertyKey};
+#[cfg(feature = "serde")]
+pub use js_serde::SerdeError;
+pub use js_symbol::{JsSymbol, WellKnownSymbol};
+pub use js_typed_array::{JsDataView, JsTypedArray, TypedArrayElement, TypedArrayKind};
 
 use flate2::bufread::GzDecoder;
 use lazy_static::lazy_static;
@@ -77,6 +106,16 @@ pub trait JsFn {
     fn call(ctx: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue;
 }
 
+unsafe extern "C" fn interrupt_handler(
+    rt: *mut JSRuntime,
+    _opaque: *mut ::std::os::raw::c_void,
+) -> ::std::os::raw::c_int {
+    match (JS_GetRuntimeOpaque(rt) as *mut super::EventLoop).as_mut() {
+        Some(event_loop) if event_loop.is_interrupted() => 1,
+        _ => 0,
+    }
+}
+
 unsafe extern "C" fn module_loader(
     ctx: *mut JSContext,
     module_name_: *const ::std::os::raw::c_char,
@@ -88,6 +127,39 @@ unsafe extern "C" fn module_loader(
     }
     let module_name = module_name.unwrap();
 
+    if !context_builder::has_fs(ctx) && !resolver::is_embedded_module(module_name) {
+        JS_ThrowReferenceError(
+            ctx,
+            "could not load module '%s', file system access is disabled\0"
+                .as_ptr()
+                .cast(),
+            module_name_,
+        );
+        return std::ptr::null_mut();
+    }
+
+    if js_bytecode::is_bytecode_file(module_name) {
+        let m =
+            resolver::import(module_name).and_then(|buf| js_bytecode::load_module_graph(ctx, &buf));
+        return match m {
+            Ok(m) => JS_VALUE_GET_PTR_real(m).cast(),
+            Err(_) => {
+                JS_ThrowReferenceError(
+                    ctx,
+                    "could not load bytecode module '%s'\0".as_ptr().cast(),
+                    module_name_,
+                );
+                std::ptr::null_mut()
+            }
+        };
+    }
+
+    if let Some(event_loop) =
+        (JS_GetRuntimeOpaque(JS_GetRuntime(ctx)) as *mut super::EventLoop).as_mut()
+    {
+        event_loop.record_loaded_file(module_name);
+    }
+
     let code = resolver::import(module_name);
 
     if code.is_err() {
@@ -128,6 +200,7 @@ unsafe extern "C" fn module_loader(
     }
 
     js_module_set_import_meta(ctx, func_val, 0, 0);
+    js_bytecode::record_module(ctx, module_name, func_val);
 
     let m = JS_VALUE_GET_PTR_real(func_val);
     JS_FreeValue_real(ctx, func_val);
@@ -141,7 +214,16 @@ impl Runtime {
     pub fn new() -> Self {
         unsafe {
             let mut rt = Runtime(JS_NewRuntime());
+            // quickjs would enforce its own 256 KiB default, far below the
+            // wasm stack, so the check stays off until `set_max_stack_size`
+            JS_SetMaxStackSize(rt.0, 0);
             JS_SetModuleLoaderFunc(rt.0, None, Some(module_loader), std::ptr::null_mut());
+            JS_SetInterruptHandler(rt.0, Some(interrupt_handler), std::ptr::null_mut());
+            JS_SetHostPromiseRejectionTracker(
+                rt.0,
+                Some(js_rejection::promise_rejection_tracker),
+                std::ptr::null_mut(),
+            );
             rt.init_event_loop();
             rt
         }
@@ -171,6 +253,61 @@ impl Runtime {
     pub fn new_context(&mut self) -> Context {
         unsafe { Context::new_with_rt(self.0) }
     }
+
+    fn event_loop(&mut self) -> Option<&mut super::EventLoop> {
+        unsafe { (JS_GetRuntimeOpaque(self.0) as *mut super::EventLoop).as_mut() }
+    }
+
+    /// Returns a handle that stops every script of this runtime. Running JS
+    /// stops at QuickJS's next interrupt check and `js_loop` after the job or
+    /// callback it is running. A `js_loop` blocked waiting for I/O only sees
+    /// the interrupt once an event or timer wakes it, or the deadline passes.
+    pub fn interrupt_handle(&mut self) -> super::InterruptHandle {
+        self.event_loop()
+            .map(|event_loop| event_loop.interrupt_handle())
+            .unwrap_or_default()
+    }
+
+    /// Interrupts the runtime like `InterruptHandle::interrupt` once
+    /// `deadline` has passed.
+    pub fn set_deadline(&mut self, deadline: std::time::Instant) {
+        if let Some(event_loop) = self.event_loop() {
+            event_loop.set_deadline(Some(deadline));
+        }
+    }
+
+    /// Picks what `js_loop` does with unhandled promise rejections, `Throw`
+    /// by default.
+    pub fn set_unhandled_rejections(&mut self, mode: super::UnhandledRejections) {
+        if let Some(event_loop) = self.event_loop() {
+            event_loop.set_unhandled_rejections(mode);
+        }
+    }
+
+    /// Caps the memory the JS heap may allocate. Allocations past the limit
+    /// fail with a catchable `InternalError: out of memory`.
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        unsafe { JS_SetMemoryLimit(self.0, limit) }
+    }
+
+    /// Caps how deep JS code may recurse, measured in bytes of native stack
+    /// from where the runtime was created. Exceeding it throws a catchable
+    /// `InternalError: stack overflow` instead of running off the wasm stack,
+    /// so it must stay below the stack size the binary is linked with.
+    /// 0 disables the check, which is the default.
+    pub fn set_max_stack_size(&mut self, size: usize) {
+        unsafe { JS_SetMaxStackSize(self.0, size) }
+    }
+
+    /// Runs the cycle collector whenever this many bytes were allocated since
+    /// the last collection.
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        unsafe { JS_SetGCThreshold(self.0, threshold) }
+    }
+
+    pub fn run_gc(&mut self) {
+        unsafe { JS_RunGC(self.0) }
+    }
 }
 
 impl Drop for Runtime {
@@ -262,26 +399,9 @@ fn js_init_dirname(ctx: &mut Context) {
         }
     }
 
-    let global = ctx.get_global();
-    let get_dirname: JsValue = ctx.wrap_function("get_dirname", js_dir_name).into();
-
-    unsafe {
-        let ctx = ctx.ctx;
-        JS_DefineProperty(
-            ctx,
-            global.0.v,
-            JS_NewAtom(ctx, "__dirname\0".as_ptr().cast()),
-            js_undefined(),
-            get_dirname.get_qjs_value(),
-            js_null(),
-            (JS_PROP_THROW
-                | JS_PROP_HAS_ENUMERABLE
-                | JS_PROP_ENUMERABLE
-                | JS_PROP_HAS_CONFIGURABLE
-                | JS_PROP_CONFIGURABLE
-                | JS_PROP_HAS_GET) as i32,
-        )
-    };
+    let mut global = ctx.get_global();
+    let get_dirname = ctx.wrap_function("get_dirname", js_dir_name);
+    global.define_accessor("__dirname", Some(get_dirname), None, PropertyFlags::DEFAULT);
 }
 
 impl Context {
@@ -311,29 +431,7 @@ impl Context {
     }
 
     unsafe fn new_with_rt(rt: *mut JSRuntime) -> Context {
-        let ctx = JS_NewContext(rt);
-        JS_AddIntrinsicBigFloat(ctx);
-        JS_AddIntrinsicBigDecimal(ctx);
-        JS_AddIntrinsicOperators(ctx);
-        JS_AddIntrinsicEval(ctx);
-        JS_AddIntrinsicDate(ctx);
-        JS_EnableBignumExt(ctx, 1);
-        js_std_add_console(ctx);
-        js_init_module_std(ctx, "std\0".as_ptr() as *const i8);
-        js_init_module_os(ctx, "qjs:os\0".as_ptr() as *const i8);
-        let mut ctx = Context { ctx };
-
-        js_init_dirname(&mut ctx);
-
-        super::modules_rs::core::init_global_function(&mut ctx);
-        super::modules_rs::core::init_ext_function(&mut ctx);
-        super::modules_rs::encoding::init_encoding_module(&mut ctx);
-        super::modules_rs::os::init_module(&mut ctx);
-        super::modules_rs::fs::init_module(&mut ctx);
-        super::modules_rs::tty::init_module(&mut ctx);
-        super::modules_rs::sys::init_module(&mut ctx);
-
-        ctx
+        ContextBuilder::new().build_with_rt(rt)
     }
 
     pub fn get_global(&mut self) -> JsObject {
@@ -361,7 +459,14 @@ impl Context {
         global.set("args", args_obj.into());
     }
 
-    pub fn eval_buf(&mut self, code: Vec<u8>, filename: &str, eval_flags: u32) -> JsValue {
+    /// Evaluates `code` and returns its value, or the uncaught exception as a
+    /// `JsError`.
+    pub fn eval_buf(
+        &mut self,
+        code: Vec<u8>,
+        filename: &str,
+        eval_flags: u32,
+    ) -> Result<JsValue, JsError> {
         unsafe {
             let ctx = self.ctx;
             let len = code.len();
@@ -388,19 +493,47 @@ impl Context {
                 )
             };
             if JS_IsException_real(val) > 0 {
-                js_std_dump_error(ctx);
+                return Err(self.take_exception());
             }
-            JsValue::from_qjs_value(ctx, val)
+            Ok(JsValue::from_qjs_value(ctx, val))
         }
     }
 
-    pub fn eval_global_str(&mut self, code: String) -> JsValue {
+    pub fn eval_global_str(&mut self, code: String) -> Result<JsValue, JsError> {
         self.eval_buf(code.into_bytes(), "<evalScript>", JS_EVAL_TYPE_GLOBAL)
     }
 
-    pub fn eval_module_str(&mut self, code: String, filename: &str) {
-        self.eval_buf(code.into_bytes(), filename, JS_EVAL_TYPE_MODULE);
+    /// Evaluates `code` as a global script of this context on behalf of
+    /// `caller`, a context on the same runtime. An exception is rethrown in
+    /// `caller` instead of being printed.
+    pub fn eval_global_str_for(
+        &mut self,
+        caller: &mut Context,
+        code: String,
+        filename: &str,
+    ) -> JsValue {
+        unsafe {
+            let len = code.len();
+            let val = JS_Eval(
+                self.ctx,
+                make_c_string(code).as_ptr(),
+                len,
+                make_c_string(filename).as_ptr(),
+                JS_EVAL_TYPE_GLOBAL as i32,
+            );
+            if JS_IsException_real(val) > 0 {
+                let e = JsValue::from_qjs_value(caller.ctx, JS_GetException(self.ctx));
+                JsValue::Exception(caller.throw_error(e))
+            } else {
+                JsValue::from_qjs_value(caller.ctx, val)
+            }
+        }
+    }
+
+    pub fn eval_module_str(&mut self, code: String, filename: &str) -> Result<JsValue, JsError> {
+        let val = self.eval_buf(code.into_bytes(), filename, JS_EVAL_TYPE_MODULE);
         self.promise_loop_poll();
+        val
     }
 
     pub fn new_function<F: JsFn>(&mut self, name: &str) -> JsFunction {
@@ -478,6 +611,21 @@ impl Context {
         }
     }
 
+    /// `Number(v)`, which throws for symbols and BigInts and calls
+    /// `valueOf` on objects.
+    pub fn value_to_number(&mut self, v: &JsValue) -> Result<f64, JsException> {
+        let mut n = 0_f64;
+        unsafe {
+            if JS_ToFloat64(self.ctx, &mut n, v.get_qjs_value()) < 0 {
+                return Err(JsException(JsRef {
+                    ctx: self.ctx,
+                    v: js_exception(),
+                }));
+            }
+        }
+        Ok(n)
+    }
+
     pub fn new_error(&mut self, msg: &str) -> JsValue {
         let msg = self.new_string(msg);
         let error = unsafe { JS_NewError(self.ctx) };
@@ -509,6 +657,15 @@ impl Context {
         }
     }
 
+    /// An `InternalError` that unwinds all running JS, skipping `catch` and
+    /// `finally` blocks.
+    pub fn throw_uncatchable_error(&mut self, msg: &str) -> JsException {
+        unsafe {
+            let v = JS_ThrowUncatchableError_real(self.ctx, make_c_string(msg).as_ptr());
+            JsException(JsRef { ctx: self.ctx, v })
+        }
+    }
+
     pub fn throw_reference_error(&mut self, msg: &str) -> JsException {
         unsafe {
             let v = JS_ThrowReferenceError(self.ctx, make_c_string(msg).as_ptr());
@@ -546,7 +703,8 @@ impl Context {
                 let err = JS_ExecutePendingJob(rt, (&mut pctx) as *mut *mut JSContext);
                 if err <= 0 {
                     if err < 0 {
-                        js_std_dump_error(pctx);
+                        let mut job_ctx = std::mem::ManuallyDrop::new(Context { ctx: pctx });
+                        eprintln!("{:#}", job_ctx.take_exception());
                     }
                     break;
                 }
@@ -554,7 +712,12 @@ impl Context {
         }
     }
 
-    pub fn js_loop(&mut self) -> std::io::Result<()> {
+    /// Runs pending jobs and the event loop until there is nothing left to do.
+    /// Stops at the first uncaught exception, whether thrown by a job or by an
+    /// event loop callback, and when the runtime is interrupted. Unhandled
+    /// promise rejections are reported as set by
+    /// `Runtime::set_unhandled_rejections`.
+    pub fn js_loop(&mut self) -> Result<(), JsError> {
         unsafe {
             let rt = self.rt();
 
@@ -564,18 +727,111 @@ impl Context {
                     let err = JS_ExecutePendingJob(rt, (&mut pctx) as *mut *mut JSContext);
                     if err <= 0 {
                         if err < 0 {
-                            js_std_dump_error(pctx);
-                            return Err(std::io::Error::from(std::io::ErrorKind::Other));
+                            let mut job_ctx = std::mem::ManuallyDrop::new(Context { ctx: pctx });
+                            return Err(job_ctx.take_exception());
                         }
                         break 'pending;
                     }
                 }
-                if self.event_loop_run_once()? == 0 {
+                // listeners may queue more jobs, run those before going on
+                if self.report_rejections()? {
+                    continue;
+                }
+                let n = self.event_loop_run_once().map_err(|e| {
+                    JsError::new(JsErrorKind::Exception, &format!("event loop failed: {}", e))
+                })?;
+                if let Some(event_loop) = self.event_loop() {
+                    if let Some(e) = event_loop.take_uncaught_exception() {
+                        return Err(e);
+                    }
+                    if event_loop.take_watched_file_change() {
+                        return Err(JsError::new(
+                            JsErrorKind::WatchedFileChanged,
+                            "watched file changed",
+                        ));
+                    }
+                    if event_loop.is_interrupted() {
+                        return Err(JsError::new(
+                            JsErrorKind::Interrupted,
+                            "script execution was interrupted",
+                        ));
+                    }
+                }
+                if n == 0 {
                     return Ok(());
                 }
             }
         }
     }
+
+    /// Returns `true` once the `Runtime::interrupt_handle` was triggered or
+    /// the `Runtime::set_deadline` deadline passed.
+    pub fn is_interrupted(&mut self) -> bool {
+        self.event_loop()
+            .map_or(false, |event_loop| event_loop.is_interrupted())
+    }
+
+    /// Returns `true` when neither the job queue nor the event loop has
+    /// anything left to run.
+    pub fn is_idle(&mut self) -> bool {
+        unsafe {
+            let has_jobs = JS_IsJobPending(self.rt()) != 0;
+            let has_tasks = self
+                .event_loop()
+                .map(|event_loop| event_loop.has_pending_tasks())
+                .unwrap_or(false);
+            !has_jobs && !has_tasks
+        }
+    }
+
+    fn process_object(&mut self) -> Option<JsObject> {
+        self.get_global().get("process").to_obj()
+    }
+
+    /// Reads `process.exitCode`, which defaults to 0 like in Node.
+    pub fn exit_code(&mut self) -> i32 {
+        match self.process_object().map(|process| process.get("exitCode")) {
+            Some(JsValue::Int(code)) => code,
+            Some(JsValue::Float(code)) => code as i32,
+            _ => 0,
+        }
+    }
+
+    /// Emits `beforeExit` on `process` with the current exit code. Listeners
+    /// may schedule more work, so callers should check [`Context::is_idle`]
+    /// afterwards before exiting.
+    pub fn emit_before_exit(&mut self) -> JsValue {
+        let code = self.exit_code();
+        match self.process_object() {
+            Some(mut process) => {
+                let event = self.new_string("beforeExit");
+                process.invoke("emit", &[event.into(), code.into()])
+            }
+            None => JsValue::UnDefined,
+        }
+    }
+
+    /// Emits `exit` on `process` (at most once) and returns the code the host
+    /// process should exit with. A `code` overrides `process.exitCode`.
+    pub fn emit_exit(&mut self, code: Option<i32>) -> i32 {
+        let mut process = match self.process_object() {
+            Some(process) => process,
+            None => return code.unwrap_or(0),
+        };
+        if let Some(code) = code {
+            process.set("exitCode", code.into());
+        }
+        if process.get("_exiting") != JsValue::Bool(true) {
+            process.set("_exiting", true.into());
+            let code = self.exit_code();
+            let event = self.new_string("exit");
+            if let JsValue::Exception(e) = process.invoke("emit", &[event.into(), code.into()]) {
+                e.dump_error();
+                process.set("exitCode", 1.into());
+            }
+        }
+        self.exit_code()
+    }
 }
 
 impl Drop for Context {
@@ -657,6 +913,19 @@ impl Drop for JsRef {
     }
 }
 
+// the result of `JS_DefineProperty` with `JS_PROP_THROW`, called right after
+// it so that the pending exception is the one it threw
+unsafe fn check_define(ctx: *mut JSContext, r: i32) -> Result<(), JsException> {
+    if r < 0 {
+        Err(JsException(JsRef {
+            ctx,
+            v: js_exception(),
+        }))
+    } else {
+        Ok(())
+    }
+}
+
 pub trait AsObject {
     fn js_ref(&self) -> &JsRef;
 
@@ -700,6 +969,177 @@ pub trait AsObject {
         }
     }
 
+    fn get_symbol(&self, key: &JsSymbol) -> JsValue {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            let atom = key.to_atom();
+            let r = JS_GetPropertyInternal(ctx, js_ref.v, atom, js_ref.v, 0);
+            JS_FreeAtom(ctx, atom);
+            JsValue::from_qjs_value(ctx, r)
+        }
+    }
+
+    fn set_symbol(&mut self, key: &JsSymbol, value: JsValue) -> JsValue {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            let atom = key.to_atom();
+            let r = JS_SetProperty_real(ctx, js_ref.v, atom, value.into_qjs_value());
+            JS_FreeAtom(ctx, atom);
+            match r {
+                1 => JsValue::Bool(true),
+                0 => JsValue::Bool(false),
+                _ => JsValue::Exception(JsException(JsRef {
+                    ctx,
+                    v: js_exception(),
+                })),
+            }
+        }
+    }
+
+    /// `Object.defineProperty(obj, key, { value, ...flags })`. Throws a
+    /// `TypeError` when the property exists and is not configurable.
+    fn define_property<'k, K: Into<PropertyKey<'k>>>(
+        &mut self,
+        key: K,
+        value: JsValue,
+        flags: PropertyFlags,
+    ) -> Result<(), JsException> {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            let atom = key.into().to_atom(ctx);
+            let bits = flags.define_bits(false) | JS_PROP_HAS_VALUE as i32;
+            let r = JS_DefineProperty(
+                ctx,
+                js_ref.v,
+                atom,
+                value.get_qjs_value(),
+                js_undefined(),
+                js_undefined(),
+                bits,
+            );
+            JS_FreeAtom(ctx, atom);
+            check_define(ctx, r)
+        }
+    }
+
+    /// `Object.defineProperty(obj, key, { get, set, ...flags })`. A missing
+    /// setter makes assignments fail, or throw in strict mode.
+    fn define_accessor<'k, K: Into<PropertyKey<'k>>>(
+        &mut self,
+        key: K,
+        getter: Option<JsFunction>,
+        setter: Option<JsFunction>,
+        flags: PropertyFlags,
+    ) -> Result<(), JsException> {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            let atom = key.into().to_atom(ctx);
+            let mut bits = flags.define_bits(true);
+            let getter = match &getter {
+                Some(f) => {
+                    bits |= JS_PROP_HAS_GET as i32;
+                    f.0.v
+                }
+                None => js_undefined(),
+            };
+            let setter = match &setter {
+                Some(f) => {
+                    bits |= JS_PROP_HAS_SET as i32;
+                    f.0.v
+                }
+                None => js_undefined(),
+            };
+            let r = JS_DefineProperty(ctx, js_ref.v, atom, js_undefined(), getter, setter, bits);
+            JS_FreeAtom(ctx, atom);
+            check_define(ctx, r)
+        }
+    }
+
+    /// `Object.getOwnPropertyDescriptor(obj, key)`, `None` when there is no
+    /// such own property.
+    fn get_own_property<'k, K: Into<PropertyKey<'k>>>(
+        &self,
+        key: K,
+    ) -> Result<Option<PropertyDescriptor>, JsException> {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            let atom = key.into().to_atom(ctx);
+            let mut desc: JSPropertyDescriptor = std::mem::zeroed();
+            let r = JS_GetOwnProperty(ctx, &mut desc, js_ref.v, atom);
+            JS_FreeAtom(ctx, atom);
+            match r {
+                0 => Ok(None),
+                1 => Ok(Some(PropertyDescriptor::from_qjs(ctx, desc))),
+                _ => Err(JsException(JsRef {
+                    ctx,
+                    v: js_exception(),
+                })),
+            }
+        }
+    }
+
+    /// `Object.getPrototypeOf(obj)`, `null` at the end of the chain.
+    fn get_prototype(&self) -> JsValue {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            // the prototype is borrowed from the object
+            let proto = JS_DupValue_real(ctx, JS_GetPrototype(ctx, js_ref.v));
+            JsValue::from_qjs_value(ctx, proto)
+        }
+    }
+
+    /// `Object.setPrototypeOf(obj, proto)` with an object or `null`.
+    fn set_prototype(&mut self, proto: &JsValue) -> Result<(), JsException> {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            match JS_SetPrototype(ctx, js_ref.v, proto.get_qjs_value()) {
+                1 => Ok(()),
+                _ => Err(JsException(JsRef {
+                    ctx,
+                    v: js_exception(),
+                })),
+            }
+        }
+    }
+
+    /// `Object.preventExtensions(obj)`.
+    fn prevent_extensions(&mut self) -> Result<(), JsException> {
+        unsafe {
+            let js_ref = self.js_ref();
+            let ctx = js_ref.ctx;
+            match JS_PreventExtensions(ctx, js_ref.v) {
+                -1 => Err(JsException(JsRef {
+                    ctx,
+                    v: js_exception(),
+                })),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// `Object.freeze(obj)`.
+    fn freeze(&mut self) -> Result<(), JsException> {
+        match js_property::call_object_method(self.js_ref(), "freeze") {
+            JsValue::Exception(e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// `Object.seal(obj)`.
+    fn seal(&mut self) -> Result<(), JsException> {
+        match js_property::call_object_method(self.js_ref(), "seal") {
+            JsValue::Exception(e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
     fn delete(&mut self, key: &str) {
         unsafe {
             let js_ref = self.js_ref();
@@ -792,6 +1232,23 @@ impl JsFunction {
             JsValue::from_qjs_value(ctx, v)
         }
     }
+
+    /// `f.call(this, ...argv)`, for methods.
+    pub fn call_with_this(&self, this: &JsValue, argv: &[JsValue]) -> JsValue {
+        unsafe {
+            let ctx = self.0.ctx;
+            let mut argv: Vec<JSValue> = argv.iter().map(|v| v.get_qjs_value()).collect();
+            let f = self.0.v;
+            let v = JS_Call(
+                ctx,
+                f,
+                this.get_qjs_value(),
+                argv.len() as i32,
+                argv.as_mut_ptr(),
+            );
+            JsValue::from_qjs_value(ctx, v)
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -973,7 +1430,7 @@ impl JsBigNum {
 
 impl PartialEq for JsBigNum {
     fn eq(&self, other: &Self) -> bool {
-        self.to_int64() == other.to_int64()
+        self.kind() == other.kind() && self.to_decimal_string() == other.to_decimal_string()
     }
 }
 
@@ -981,8 +1438,11 @@ impl PartialEq for JsBigNum {
 pub struct JsException(JsRef);
 
 impl JsException {
+    /// Prints the pending exception like `js_std_dump_error`, with stack
+    /// frames mapped to the original sources.
     pub fn dump_error(&self) {
-        unsafe { js_std_dump_error(self.0.ctx) }
+        let mut ctx = std::mem::ManuallyDrop::new(Context { ctx: self.0.ctx });
+        eprintln!("{:#}", ctx.take_exception());
     }
 }
 
@@ -997,8 +1457,14 @@ pub enum JsValue {
     Array(JsArray),
     Promise(JsPromise),
     ArrayBuffer(JsArrayBuffer),
+    TypedArray(JsTypedArray),
+    DataView(JsDataView),
+    Date(JsDate),
+    RegExp(JsRegExp),
+    Map(JsMap),
+    Set(JsSet),
     Function(JsFunction),
-    Symbol(JsRef),
+    Symbol(JsSymbol),
     Bool(bool),
     Null,
     UnDefined,
@@ -1035,7 +1501,24 @@ impl JsValue {
                     } else if JS_IsPromise(ctx, v) != 0 {
                         JsValue::Promise(JsPromise(JsRef { ctx, v }))
                     } else {
-                        JsValue::Object(JsObject(JsRef { ctx, v }))
+                        match js_typed_array::buffer_kind(v) {
+                            JS_BUFFER_KIND_NONE => match js_builtins::object_kind(v) {
+                                JS_OBJECT_KIND_DATE => JsValue::Date(JsDate(JsRef { ctx, v })),
+                                JS_OBJECT_KIND_REGEXP => {
+                                    JsValue::RegExp(JsRegExp(JsRef { ctx, v }))
+                                }
+                                JS_OBJECT_KIND_MAP => JsValue::Map(JsMap(JsRef { ctx, v })),
+                                JS_OBJECT_KIND_SET => JsValue::Set(JsSet(JsRef { ctx, v })),
+                                _ => JsValue::Object(JsObject(JsRef { ctx, v })),
+                            },
+                            JS_BUFFER_KIND_ARRAY_BUFFER => {
+                                JsValue::ArrayBuffer(JsArrayBuffer(JsRef { ctx, v }))
+                            }
+                            JS_BUFFER_KIND_DATA_VIEW => {
+                                JsValue::DataView(JsDataView(JsRef { ctx, v }))
+                            }
+                            _ => JsValue::TypedArray(JsTypedArray(JsRef { ctx, v })),
+                        }
                     }
                 }
                 JS_TAG_BOOL => JsValue::Bool(JS_ToBool(ctx, v) != 0),
@@ -1045,7 +1528,7 @@ impl JsValue {
                 JS_TAG_FUNCTION_BYTECODE => {
                     JsValue::FunctionByteCode(JsFunctionByteCode(JsRef { ctx, v }))
                 }
-                JS_TAG_SYMBOL => JsValue::Symbol(JsRef { ctx, v }),
+                JS_TAG_SYMBOL => JsValue::Symbol(JsSymbol(JsRef { ctx, v })),
                 _ => JsValue::Other(JsRef { ctx, v }),
             }
         }
@@ -1064,6 +1547,12 @@ impl JsValue {
                 JsValue::Object(JsObject(JsRef { v, .. })) => *v,
                 JsValue::Array(JsArray(JsRef { v, .. })) => *v,
                 JsValue::ArrayBuffer(JsArrayBuffer(JsRef { v, .. })) => *v,
+                JsValue::TypedArray(JsTypedArray(JsRef { v, .. })) => *v,
+                JsValue::DataView(JsDataView(JsRef { v, .. })) => *v,
+                JsValue::Date(JsDate(JsRef { v, .. })) => *v,
+                JsValue::RegExp(JsRegExp(JsRef { v, .. })) => *v,
+                JsValue::Map(JsMap(JsRef { v, .. })) => *v,
+                JsValue::Set(JsSet(JsRef { v, .. })) => *v,
                 JsValue::Function(JsFunction(JsRef { v, .. })) => *v,
                 JsValue::Promise(JsPromise(JsRef { v, .. })) => *v,
                 JsValue::Bool(b) => JS_NewBool_real(std::ptr::null_mut(), if *b { 1 } else { 0 }),
@@ -1072,7 +1561,7 @@ impl JsValue {
                 JsValue::Exception(JsException(JsRef { v, .. })) => *v,
                 JsValue::FunctionByteCode(JsFunctionByteCode(JsRef { v, .. })) => *v,
                 JsValue::Other(JsRef { v, .. }) => *v,
-                JsValue::Symbol(JsRef { v, .. }) => *v,
+                JsValue::Symbol(JsSymbol(JsRef { v, .. })) => *v,
             }
         }
     }
@@ -1197,6 +1686,48 @@ impl From<JsArrayBuffer> for JsValue {
     }
 }
 
+impl From<JsTypedArray> for JsValue {
+    fn from(v: JsTypedArray) -> Self {
+        Self::TypedArray(v)
+    }
+}
+
+impl From<JsDataView> for JsValue {
+    fn from(v: JsDataView) -> Self {
+        Self::DataView(v)
+    }
+}
+
+impl From<JsDate> for JsValue {
+    fn from(v: JsDate) -> Self {
+        Self::Date(v)
+    }
+}
+
+impl From<JsRegExp> for JsValue {
+    fn from(v: JsRegExp) -> Self {
+        Self::RegExp(v)
+    }
+}
+
+impl From<JsMap> for JsValue {
+    fn from(v: JsMap) -> Self {
+        Self::Map(v)
+    }
+}
+
+impl From<JsSet> for JsValue {
+    fn from(v: JsSet) -> Self {
+        Self::Set(v)
+    }
+}
+
+impl From<JsSymbol> for JsValue {
+    fn from(v: JsSymbol) -> Self {
+        Self::Symbol(v)
+    }
+}
+
 impl From<JsFunction> for JsValue {
     fn from(v: JsFunction) -> Self {
         Self::Function(v)
@@ -1,3 +1,4 @@
+use crate::js_bytecode::is_bytecode_file;
 use crate::transpiler::{tsx_to_js_str, tsx_to_js_vec, OutputType};
 use flate2::bufread::GzDecoder;
 use lazy_static::lazy_static;
@@ -38,7 +39,7 @@ pub fn resolve(module_name: &str) -> Result<String, Error> {
             path.set_extension("js");
             Ok(path.to_str().unwrap().to_string())
         }
-        "js" | "ts" | "tsx" | "jsx" | "cjs" | "mjs" | "cts" | "mts" | "zrc" => {
+        "js" | "ts" | "tsx" | "jsx" | "cjs" | "mjs" | "cts" | "mts" | "zrc" | "qjsbc" => {
             Ok(path.to_str().unwrap().to_string())
         }
         _ => Err(Error::new(
@@ -69,6 +70,9 @@ pub fn import(module_name: &str) -> Result<Vec<u8>, Error> {
     if is_embedded_module(module_name) {
         let path = PathBuf::from("modules").join(path?);
         read_embedded_module(&path)
+    } else if is_bytecode_file(module_name) {
+        // precompiled by `drop compile`, hand back the raw bytes
+        fs::read(path?)
     } else {
         let buf = fs::read(path.unwrap());
         tsx_to_js_vec(
@@ -80,7 +84,7 @@ pub fn import(module_name: &str) -> Result<Vec<u8>, Error> {
     }
 }
 
-fn is_embedded_module(module_name_or_path: &str) -> bool {
+pub fn is_embedded_module(module_name_or_path: &str) -> bool {
     let resolved = resolve(module_name_or_path).unwrap_or("".to_string());
     EMBEDDED_MODULES_LIST
         .iter()
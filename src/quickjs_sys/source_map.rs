@@ -0,0 +1,60 @@
+//! Source maps of transpiled modules, used to point stack traces at the
+//! original `.ts`/`.tsx` sources.
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use sourcemap::SourceMap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+lazy_static! {
+    // QuickJS frames look like `    at foo (file.ts:12)`, newer versions add
+    // the column as in `(file.ts:12:5)`
+    static ref FRAME_LOCATION_REGEX: Regex = Regex::new(r"\(([^()]+?):(\d+)(?::(\d+))?\)").unwrap();
+}
+
+thread_local! {
+    // keyed by the filename the module is evaluated with
+    static SOURCE_MAPS: RefCell<HashMap<String, SourceMap>> = RefCell::new(HashMap::new());
+}
+
+/// Keeps the source map of the code transpiled from `filename`, replacing
+/// the one of a previous version.
+pub fn register(filename: &str, map: SourceMap) {
+    SOURCE_MAPS.with(|maps| {
+        maps.borrow_mut().insert(filename.to_string(), map);
+    });
+}
+
+/// Maps a 1-based position in the code transpiled from `filename` back to
+/// the original source. Without a column the first mapping on the line is
+/// used.
+pub fn lookup(filename: &str, line: u32, col: Option<u32>) -> Option<(String, u32, u32)> {
+    SOURCE_MAPS.with(|maps| {
+        let maps = maps.borrow();
+        let map = maps.get(filename)?;
+        let line = line.checked_sub(1)?;
+        let token = match col {
+            Some(col) => map.lookup_token(line, col.saturating_sub(1))?,
+            None => map.tokens().find(|token| token.get_dst_line() == line)?,
+        };
+        let source = token.get_source().unwrap_or(filename).to_string();
+        Some((source, token.get_src_line() + 1, token.get_src_col() + 1))
+    })
+}
+
+/// Rewrites the frames of a QuickJS `stack` that point into transpiled code to
+/// `file:line:col` in the original source. Other frames are left as they are.
+pub fn rewrite_stack(stack: &str) -> String {
+    FRAME_LOCATION_REGEX
+        .replace_all(stack, |caps: &Captures| {
+            let filename = &caps[1];
+            let line = caps[2].parse().ok();
+            let col = caps.get(3).and_then(|col| col.as_str().parse().ok());
+            match line.and_then(|line| lookup(filename, line, col)) {
+                Some((source, line, col)) => format!("({}:{}:{})", source, line, col),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
@@ -11,11 +11,15 @@ use swc_common::comments::SingleThreadedComments;
 use swc_common::errors::ColorConfig;
 use swc_common::errors::Handler;
 use swc_common::sync::Lrc;
+use swc_common::BytePos;
 use swc_common::FileName;
 use swc_common::Globals;
+use swc_common::LineCol;
 use swc_common::Mark;
 use swc_common::SourceMap;
+use swc_common::Spanned;
 use swc_common::GLOBALS;
+use swc_ecma_ast::{CallExpr, Callee, Expr, Lit, Module};
 use swc_ecma_codegen::text_writer::JsWriter;
 use swc_ecma_codegen::Emitter;
 use swc_ecma_parser::lexer::Lexer;
@@ -25,15 +29,21 @@ use swc_ecma_parser::Syntax;
 use swc_ecma_parser::TsConfig;
 use swc_ecma_transforms_base::feature::FeatureFlag;
 use swc_ecma_transforms_base::fixer::fixer;
+use swc_ecma_transforms_base::helpers::inject_helpers;
 use swc_ecma_transforms_base::helpers::Helpers;
 use swc_ecma_transforms_base::helpers::HELPERS;
 use swc_ecma_transforms_base::hygiene::hygiene;
+use swc_ecma_transforms_base::resolver;
 use swc_ecma_transforms_module::common_js::{self, common_js};
 use swc_ecma_transforms_react::react;
 use swc_ecma_transforms_react::Options;
 use swc_ecma_transforms_typescript::strip;
 use swc_ecma_visit::Fold;
 use swc_ecma_visit::FoldWith;
+use swc_ecma_visit::Visit;
+use swc_ecma_visit::VisitWith;
+
+use super::source_map;
 
 lazy_static! {
     static ref PRAGMA_REGEX: Regex = Regex::new(r"@jsx\s+([^\s]+)").unwrap();
@@ -87,46 +97,178 @@ fn tsx_to_esmodule_transform(cm: &Rc<SourceMap>, pragma: Option<String>) -> impl
     )
 }
 
-pub fn tsx_to_js_vec(filename: Option<&str>, source: &str, output: &OutputType) -> Result<Vec<u8>> {
-    let globals = Globals::default();
-    let cm: Lrc<SourceMap> = Default::default();
-    let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
+// Unlike `tsx_to_commonjs_transform`, types are stripped before the module
+// transform so type-only imports never turn into `require` calls, and helpers
+// are inlined since a bundle has nowhere to import them from.
+fn tsx_to_bundle_transform(cm: &Rc<SourceMap>, pragma: Option<String>) -> impl Fold {
+    let unresolved_mark = Mark::new();
+    let top_level_mark = Mark::new();
+    chain!(
+        resolver(unresolved_mark, top_level_mark, true),
+        react::<SingleThreadedComments>(
+            cm.clone(),
+            None,
+            Options {
+                pragma,
+                ..Default::default()
+            },
+            top_level_mark,
+        ),
+        strip(top_level_mark),
+        common_js::<SingleThreadedComments>(
+            unresolved_mark,
+            Default::default(),
+            FeatureFlag::default(),
+            None
+        ),
+        inject_helpers(unresolved_mark),
+        hygiene(),
+        fixer(None),
+    )
+}
 
-    let filename = match filename {
+/// Collects the specifier of every `require("...")` call with a string literal.
+#[derive(Default)]
+struct RequireCollector {
+    specifiers: Vec<String>,
+}
+
+impl Visit for RequireCollector {
+    fn visit_call_expr(&mut self, n: &CallExpr) {
+        if let Callee::Expr(callee) = &n.callee {
+            if let Expr::Ident(ident) = &**callee {
+                if &*ident.sym == "require" {
+                    if let Some(Expr::Lit(Lit::Str(s))) = n.args.get(0).map(|a| &*a.expr) {
+                        let specifier = s.value.to_string();
+                        if !self.specifiers.contains(&specifier) {
+                            self.specifiers.push(specifier);
+                        }
+                    }
+                }
+            }
+        }
+        n.visit_children_with(self);
+    }
+}
+
+fn tsx_syntax() -> Syntax {
+    Syntax::Typescript(TsConfig {
+        tsx: true,
+        decorators: true,
+        no_early_errors: true,
+        ..Default::default()
+    })
+}
+
+fn to_file_name(filename: Option<&str>) -> FileName {
+    match filename {
         Some(filename) => FileName::Custom(filename.into()),
         None => FileName::Anon,
-    };
+    }
+}
 
-    let fm = cm.new_source_file(filename, source.into());
+/// A parse error located in the original source. `line` and `col` are 1-based.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+/// Parses `source` with the same syntax options as `tsx_to_js_vec` and returns
+/// every diagnostic the parser reports, without transforming or emitting code.
+pub fn check_syntax(filename: Option<&str>, source: &str) -> Vec<Diagnostic> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(to_file_name(filename), source.into());
 
     let lexer = Lexer::new(
-        Syntax::Typescript(TsConfig {
-            tsx: true,
-            decorators: true,
-            no_early_errors: true,
-            ..Default::default()
-        }),
+        tsx_syntax(),
         swc_ecma_ast::EsVersion::latest(),
         StringInput::from(&*fm),
         None,
     );
 
     let mut parser = Parser::new_from(lexer);
+    let fatal = parser.parse_module().err();
+
+    let mut errors = parser.take_errors();
+    errors.extend(fatal);
+    errors.sort_by_key(|e| e.span().lo);
+
+    errors
+        .iter()
+        .map(|e| {
+            let loc = cm.lookup_char_pos(e.span().lo);
+            Diagnostic {
+                line: loc.line,
+                col: loc.col.0 + 1,
+                message: e.kind().msg().to_string(),
+            }
+        })
+        .collect()
+}
+
+fn parse_module(cm: &Lrc<SourceMap>, filename: Option<&str>, source: &str) -> Result<Module> {
+    let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
+
+    let fm = cm.new_source_file(to_file_name(filename), source.into());
 
-    let module = match parser
+    let lexer = Lexer::new(
+        tsx_syntax(),
+        swc_ecma_ast::EsVersion::latest(),
+        StringInput::from(&*fm),
+        None,
+    );
+
+    let mut parser = Parser::new_from(lexer);
+
+    match parser
         .parse_module()
         .map_err(|e| e.into_diagnostic(&handler).emit())
     {
-        Ok(module) => module,
+        Ok(module) => Ok(module),
         Err(_) => bail!("JSX compilation failed."),
-    };
+    }
+}
 
+// Only output without a filename is minified. Code that runs with one keeps
+// its lines, since QuickJS stack frames carry a line number but no column, and
+// a minified module is one line that `mappings` can't tell apart.
+fn emit_module(
+    cm: &Lrc<SourceMap>,
+    module: &Module,
+    mappings: Option<&mut Vec<(BytePos, LineCol)>>,
+) -> Vec<u8> {
     let mut buffer = vec![];
+    {
+        let mut emitter = Emitter {
+            cfg: swc_ecma_codegen::Config {
+                minify: mappings.is_none(),
+                ..swc_ecma_codegen::Config::default()
+            },
+            cm: cm.clone(),
+            comments: None,
+            wr: JsWriter::new(cm.clone(), "\n", &mut buffer, mappings),
+        };
+        emitter.emit_module(module).unwrap();
+    }
+    buffer
+}
 
-    let pragma = PRAGMA_REGEX
+fn jsx_pragma(source: &str) -> Option<String> {
+    PRAGMA_REGEX
         .find_iter(source)
         .next()
-        .map(|m| m.as_str().to_string().replace("@jsx ", ""));
+        .map(|m| m.as_str().to_string().replace("@jsx ", ""))
+}
+
+/// Transpiles `source` to JS. With a `filename` the source map of the output
+/// is kept to rewrite stack traces, see `source_map`.
+pub fn tsx_to_js_vec(filename: Option<&str>, source: &str, output: &OutputType) -> Result<Vec<u8>> {
+    let globals = Globals::default();
+    let cm: Lrc<SourceMap> = Default::default();
+    let module = parse_module(&cm, filename, source)?;
+    let pragma = jsx_pragma(source);
 
     GLOBALS.set(&globals, || {
         let helpers = Helpers::new(false);
@@ -139,19 +281,36 @@ pub fn tsx_to_js_vec(filename: Option<&str>, source: &str, output: &OutputType)
                     module.fold_with(&mut tsx_to_esmodule_transform(&cm, pragma.clone()))
                 }
             };
-            let mut emitter = Emitter {
-                cfg: swc_ecma_codegen::Config {
-                    minify: true,
-                    ..swc_ecma_codegen::Config::default()
-                },
-                cm: cm.clone(),
-                comments: None,
-                wr: JsWriter::new(cm, "\n", &mut buffer, None),
+            let filename = match filename {
+                Some(filename) => filename,
+                None => return Ok(emit_module(&cm, &module, None)),
             };
-            emitter.emit_module(&module).unwrap();
-        });
-    });
-    Ok(buffer)
+            let mut mappings = vec![];
+            let code = emit_module(&cm, &module, Some(&mut mappings));
+            source_map::register(filename, cm.build_source_map(&mut mappings));
+            Ok(code)
+        })
+    })
+}
+
+/// Transpiles `source` to a CommonJS module body for the bundler and returns it
+/// together with the specifiers it `require`s, in the order they appear.
+pub fn tsx_to_bundle_module(filename: Option<&str>, source: &str) -> Result<(String, Vec<String>)> {
+    let globals = Globals::default();
+    let cm: Lrc<SourceMap> = Default::default();
+    let module = parse_module(&cm, filename, source)?;
+    let pragma = jsx_pragma(source);
+
+    GLOBALS.set(&globals, || {
+        let helpers = Helpers::new(false);
+        HELPERS.set(&helpers, || {
+            let module = module.fold_with(&mut tsx_to_bundle_transform(&cm, pragma));
+            let mut collector = RequireCollector::default();
+            module.visit_with(&mut collector);
+            let code = String::from_utf8(emit_module(&cm, &module, None))?;
+            Ok((code, collector.specifiers))
+        })
+    })
 }
 
 pub fn tsx_to_js_str(filename: Option<&str>, source: &str, output: &OutputType) -> Result<String> {
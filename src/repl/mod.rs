@@ -0,0 +1,221 @@
+use crate::quickjs_sys::transpiler::{tsx_to_js_str, OutputType};
+use crate::quickjs_sys::*;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+const COMMANDS: [&str; 5] = [".break", ".exit", ".help", ".load", ".save"];
+
+const HELP: &str = ".break    Sometimes you get stuck, this gets you out
+.exit     Exit the REPL
+.help     Print this help message
+.load     Load JS/TS from a file into the REPL session
+.save     Save all evaluated commands in this REPL session to a file
+
+Press Ctrl+D to exit the REPL";
+
+/// Scans `source` for brackets, strings, template literals and block comments
+/// that are still open, which means the user is not done typing yet.
+fn is_incomplete(source: &str) -> bool {
+    let mut depth: Vec<char> = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth.push(c),
+            ')' | ']' | '}' => {
+                // unbalanced closers are a syntax error, let the parser report it
+                if depth.pop().is_none() {
+                    return false;
+                }
+            }
+            '\'' | '"' | '`' => {
+                let mut closed = false;
+                while let Some(s) = chars.next() {
+                    if s == '\\' {
+                        chars.next();
+                    } else if s == c {
+                        closed = true;
+                        break;
+                    } else if s == '\n' && c != '`' {
+                        return false;
+                    }
+                }
+                if !closed {
+                    return true;
+                }
+            }
+            '/' => match chars.peek() {
+                Some('/') => {
+                    while let Some(s) = chars.next() {
+                        if s == '\n' {
+                            break;
+                        }
+                    }
+                }
+                Some('*') => {
+                    chars.next();
+                    let mut closed = false;
+                    while let Some(s) = chars.next() {
+                        if s == '*' && chars.peek() == Some(&'/') {
+                            chars.next();
+                            closed = true;
+                            break;
+                        }
+                    }
+                    if !closed {
+                        return true;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    !depth.is_empty() || source.trim_end().ends_with('\\')
+}
+
+pub struct Repl<'a> {
+    ctx: &'a mut Context,
+    inspect: Option<JsFunction>,
+    history: Vec<String>,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(ctx: &'a mut Context) -> Self {
+        let inspect = ctx.eval_global_str("import('internal/util/inspect.js')".into());
+        if let Err(e) = ctx.js_loop() {
+            eprintln!("{:#}", e);
+        }
+        let inspect = match inspect {
+            Ok(JsValue::Promise(p)) => p.get_result().get("inspect").and_then(JsValue::to_function),
+            _ => None,
+        };
+        Repl {
+            ctx,
+            inspect,
+            history: vec![],
+        }
+    }
+
+    fn print_value(&mut self, val: JsValue) {
+        let val = match val {
+            JsValue::Promise(p) => p.get_result(),
+            val => val,
+        };
+        let mut global = self.ctx.get_global();
+        global.set("_", val.clone());
+        let printed = match &self.inspect {
+            Some(inspect) => {
+                let mut opts = self.ctx.new_object();
+                opts.set("colors", (unsafe { libc::isatty(1) } == 1).into());
+                inspect.call(&[val, opts.into()])
+            }
+            None => self.ctx.value_to_string(&val),
+        };
+        match printed {
+            JsValue::String(s) => println!("{}", s.as_str()),
+            JsValue::Exception(e) => e.dump_error(),
+            _ => {}
+        }
+    }
+
+    /// Transpiles and evaluates one complete entry, then drives the event
+    /// loop so that promises and timers it started get to settle.
+    pub fn eval(&mut self, source: &str) {
+        let trimmed = source.trim();
+        // `{ a: 1 }` is a block statement to the parser but an object to the user
+        let source = if trimmed.starts_with('{') && trimmed.ends_with('}') {
+            format!("({})", trimmed)
+        } else {
+            source.to_string()
+        };
+        let code = match tsx_to_js_str(Some("<repl>"), &source, &OutputType::ESModule) {
+            Ok(code) => code,
+            // diagnostics were already printed by the transpiler
+            Err(_) => return,
+        };
+        let val = self.ctx.eval_global_str(code);
+        if let Err(e) = self.ctx.js_loop() {
+            eprintln!("{:#}", e);
+        }
+        match val {
+            Ok(val) => self.print_value(val),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                self.eval(&source);
+                self.history.push(source);
+            }
+            Err(e) => eprintln!("Failed to load: {}: {}", path, e),
+        }
+    }
+
+    fn save(&mut self, path: &str) {
+        match fs::write(path, self.history.join("\n")) {
+            Ok(_) => println!("Session saved to: {}", path),
+            Err(e) => eprintln!("Failed to save: {}: {}", path, e),
+        }
+    }
+
+    /// Runs the read-eval-print loop until `.exit` or end of input and
+    /// returns the process exit code.
+    pub fn run(&mut self) -> i32 {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        let mut buffer = String::new();
+        loop {
+            let prompt = if buffer.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            print!("{}", prompt);
+            if let Err(e) = io::stdout().flush() {
+                eprintln!("Failed to write to stdout: {}", e);
+                break;
+            }
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                _ => break,
+            };
+            let mut parts = line.trim().splitn(2, char::is_whitespace);
+            let command = parts.next().unwrap_or_default();
+            let arg = parts.next().map(str::trim);
+            // anything else starting with a dot, like `.5`, is evaluated as JS
+            if buffer.is_empty() && COMMANDS.contains(&command) {
+                match (command, arg) {
+                    (".exit", _) => break,
+                    (".help", _) => println!("{}", HELP),
+                    (".break", _) => {}
+                    (".load", Some(path)) => self.load(path),
+                    (".save", Some(path)) => self.save(path),
+                    _ => eprintln!("{} needs a file name", command),
+                }
+                continue;
+            }
+            if line.trim() == ".break" {
+                buffer.clear();
+                continue;
+            }
+            buffer.push_str(&line);
+            buffer.push('\n');
+            if is_incomplete(&buffer) {
+                continue;
+            }
+            let source = std::mem::take(&mut buffer);
+            if source.trim().is_empty() {
+                continue;
+            }
+            self.eval(&source);
+            self.history.push(source.trim_end().to_string());
+        }
+        println!();
+        self.ctx.emit_exit(None)
+    }
+}
@@ -48,6 +48,8 @@ const createConfig = (mod) => (_, { mode = "production" }) => {
 				"tty",
 				"url",
 				"util",
+				"v8",
+				"vm",
 			]
 				.concat(ALL_PACKAGES.filter((p) => p !== mod))
 				.reduce((acc, curr) => ((acc[curr] = curr), acc), {}),